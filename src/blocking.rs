@@ -0,0 +1,391 @@
+//! A blocking client for FuzzySearch, for use outside of an async runtime.
+//!
+//! This should not be used from within an async runtime, as reqwest will
+//! panic when a blocking client is used in an async context.
+
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+#[cfg(feature = "local_hash")]
+use crate::{attribute_transforms, transform_hashes};
+use crate::{
+    cache, dedup_files, fill_searched_hash, group_by_searched_hash, hashes_param, parse_response,
+    rate_limit::RateLimiter, trace_headers, Cache, CacheKey, Error, File, FurAffinityFileDetail,
+    FuzzySearchBuilder, MatchType, Quota, RetryPolicy,
+};
+
+/// FuzzySearch is a collection of methods to get information from fuzzysearch.net.
+///
+/// It mirrors [crate::FuzzySearch], but all methods block the current thread.
+/// Clients are configured with [crate::FuzzySearch::builder] and created with
+/// [FuzzySearchBuilder::build_blocking], as [FuzzySearchBuilder::build]
+/// creates an async client.
+#[derive(Clone)]
+pub struct FuzzySearch {
+    pub(crate) endpoint: Arc<str>,
+    pub(crate) api_key: Arc<str>,
+    pub(crate) client: reqwest::blocking::Client,
+    pub(crate) headers: Arc<reqwest::header::HeaderMap>,
+    pub(crate) default_distance: Option<i64>,
+    pub(crate) default_match_type: Option<MatchType>,
+    pub(crate) hash_chunk_size: usize,
+    pub(crate) retry_policy: Arc<RetryPolicy>,
+    pub(crate) rate_limiter: Arc<RateLimiter>,
+    pub(crate) cache: Option<cache::ResponseCache>,
+}
+
+impl std::fmt::Debug for FuzzySearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FuzzySearch")
+            .field("endpoint", &self.endpoint)
+            .field("api_key", &"[redacted]")
+            .field("default_distance", &self.default_distance)
+            .field("default_match_type", &self.default_match_type)
+            .field("retry_policy", &self.retry_policy)
+            .field("quota", &self.quota())
+            .field(
+                "cache",
+                &self.cache.as_ref().map(|cache| cache.cache.stats()),
+            )
+            .finish_non_exhaustive()
+    }
+}
+
+impl FuzzySearch {
+    /// Create a new blocking FuzzySearch instance. Requires the API key.
+    pub fn new(api_key: String) -> Self {
+        FuzzySearchBuilder::default()
+            .api_key(api_key)
+            .build_blocking()
+            .expect("default options should always be valid")
+    }
+
+    /// The remaining quota for the API key in whichever of the API's rate
+    /// limit buckets has the fewest requests left.
+    pub fn quota(&self) -> Quota {
+        self.rate_limiter.quota()
+    }
+
+    /// The response cache, if one was configured.
+    pub fn cache(&self) -> Option<&dyn Cache> {
+        self.cache.as_ref().map(|cache| cache.cache.as_ref())
+    }
+
+    /// Remove a response from the cache, returning if it existed.
+    ///
+    /// The key is used for this client's endpoint, so it does not need a base
+    /// URL.
+    pub fn invalidate(&self, key: &CacheKey) -> bool {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.invalidate(key))
+            .unwrap_or(false)
+    }
+
+    /// Makes a request against the API. It deserializes the JSON response.
+    fn make_request<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        params: &HashMap<&str, String>,
+    ) -> Result<T, Error> {
+        let url = format!("{}{}", self.endpoint, endpoint);
+
+        self.send(endpoint, || {
+            self.client
+                .get(&url)
+                .headers(self.headers.as_ref().clone())
+                .header("x-api-key", self.api_key.as_bytes())
+                .query(params)
+        })
+    }
+
+    /// Sends a request, retrying according to the retry policy.
+    fn send<T, F>(&self, endpoint: &str, build: F) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> reqwest::blocking::RequestBuilder,
+    {
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            while let Err(wait) = self.rate_limiter.try_acquire(endpoint) {
+                std::thread::sleep(wait);
+            }
+            let req = build().headers(trace_headers());
+
+            let res = req.send().map_err(Error::from).and_then(|resp| {
+                let status = resp.status();
+                let headers = resp.headers().clone();
+                self.rate_limiter.update(endpoint, status, &headers);
+
+                let body = resp.bytes()?;
+                parse_response(status, &headers, &body)
+            });
+
+            match res {
+                Err(err) if self.retry_policy.should_retry(&err, attempt) => {
+                    let delay = self.retry_policy.delay(&err, attempt);
+
+                    #[cfg(feature = "trace")]
+                    tracing::warn!(attempt, ?delay, "request failed, retrying: {}", err);
+
+                    std::thread::sleep(delay);
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Attempt to lookup multiple hashes.
+    ///
+    /// Hashes are split into chunks the API accepts and requested one after
+    /// another, with results returned in the same order as the hashes. If a
+    /// cache is configured, only hashes without cached results are requested.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_hashes(&self, hashes: &[i64], distance: Option<i64>) -> Result<Vec<File>, Error> {
+        let distance = distance.or(self.default_distance);
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.fetch_hashes(hashes, distance),
+        };
+
+        let (cached, misses) = cache.split_hashes(hashes, distance);
+        let files = self.fetch_hashes(&misses, distance)?;
+
+        Ok(cache.merge_hashes(hashes, distance, cached, &misses, files))
+    }
+
+    /// Request hashes from the API in chunks.
+    fn fetch_hashes(&self, hashes: &[i64], distance: Option<i64>) -> Result<Vec<File>, Error> {
+        let mut files = Vec::new();
+        for chunk in hashes.chunks(self.hash_chunk_size) {
+            let mut params = HashMap::new();
+            params.insert("hash", hashes_param(chunk));
+            if let Some(distance) = distance {
+                params.insert("distance", distance.to_string());
+            }
+
+            let mut chunk_files: Vec<File> = self.make_request("/hashes", &params)?;
+            fill_searched_hash(chunk, &mut chunk_files);
+            files.extend(chunk_files);
+        }
+
+        Ok(files)
+    }
+
+    /// Attempt to lookup multiple hashes, returning each submission only once.
+    ///
+    /// This is useful for hashes of many frames of the same animation, which
+    /// are likely to find the same submissions.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_hashes_deduped(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<File>, Error> {
+        let files = self.lookup_hashes(hashes, distance)?;
+
+        Ok(dedup_files(files))
+    }
+
+    /// Attempt to lookup the hashes of transformed images, such as those from
+    /// [crate::hash_transforms], returning which transform found each result.
+    ///
+    /// If multiple transforms produced the same hash, results are reported
+    /// for the first of them.
+    #[cfg(feature = "local_hash")]
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_transforms(
+        &self,
+        hashes: &[(crate::Transform, i64)],
+        distance: Option<i64>,
+    ) -> Result<Vec<(crate::Transform, File)>, Error> {
+        let searched = transform_hashes(hashes);
+        let groups = self.lookup_hashes_grouped(&searched, distance)?;
+
+        Ok(attribute_transforms(hashes, groups))
+    }
+
+    /// Attempt to lookup multiple hashes, grouping results by the hash that
+    /// found them.
+    ///
+    /// Groups are in the same order as the hashes and every hash has a group,
+    /// even if no results were found for it.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_hashes_grouped(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<(i64, Vec<File>)>, Error> {
+        let files = self.lookup_hashes(hashes, distance)?;
+
+        Ok(group_by_searched_hash(hashes, files))
+    }
+
+    /// Attempt to perform a search using an image URL.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_url(&self, url: &str) -> Result<Vec<File>, Error> {
+        let key = CacheKey::url(url);
+        if let Some(files) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(files);
+        }
+
+        let mut params = HashMap::new();
+        params.insert("url", url.to_string());
+
+        let files: Vec<File> = self.make_request("/url", &params)?;
+        if let Some(cache) = &self.cache {
+            cache.insert(key, files.clone());
+        }
+
+        Ok(files)
+    }
+
+    /// Attempt to reverse image search.
+    ///
+    /// Requiring an exact match will be faster, but potentially leave out results.
+    /// If no match type is provided, the default match type is used, falling
+    /// back to [MatchType::Close].
+    #[cfg_attr(
+        feature = "trace",
+        tracing::instrument(err, skip(self, data, exact), fields(exact))
+    )]
+    pub fn image_search(
+        &self,
+        data: &[u8],
+        exact: impl Into<Option<MatchType>>,
+        distance: Option<i64>,
+    ) -> Result<Vec<File>, Error> {
+        use reqwest::blocking::multipart::{Form, Part};
+
+        let url = format!("{}/image", self.endpoint);
+
+        let exact = exact
+            .into()
+            .or(self.default_match_type)
+            .unwrap_or(MatchType::Close);
+
+        #[cfg(feature = "trace")]
+        tracing::Span::current().record("exact", tracing::field::debug(&exact));
+
+        let distance = distance.or(self.default_distance);
+
+        let key = CacheKey::image(data, exact, distance);
+        if let Some(files) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(files);
+        }
+
+        let mut query = vec![("type", exact.as_str().to_string())];
+        if let Some(distance) = distance {
+            query.push(("distance", distance.to_string()));
+        }
+
+        let files: Vec<File> = self.send("/image", || {
+            let part = Part::bytes(Vec::from(data));
+            let form = Form::new().part("image", part);
+
+            self.client
+                .post(&url)
+                .query(&query)
+                .headers(self.headers.as_ref().clone())
+                .header("x-api-key", self.api_key.as_bytes())
+                .multipart(form)
+        })?;
+
+        if let Some(cache) = &self.cache {
+            cache.insert(key, files.clone());
+        }
+
+        Ok(files)
+    }
+
+    /// Attempt to resolve some information from a FurAffinity file.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub fn lookup_furaffinity_file(&self, url: &str) -> Result<Vec<FurAffinityFileDetail>, Error> {
+        let mut params = HashMap::new();
+        params.insert("search", url.to_string());
+
+        self.make_request("/file/furaffinity", &params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::file_json;
+    use std::io::{Read, Write};
+
+    /// Start a server that answers each connection with the next status and
+    /// body, returning the endpoint.
+    fn mock_server(responses: Vec<(u16, String)>) -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        std::thread::spawn(move || {
+            for (status, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+
+                let mut buf = [0u8; 4096];
+                let len = stream.read(&mut buf).unwrap();
+                assert!(String::from_utf8_lossy(&buf[..len]).contains("x-api-key: test"));
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        endpoint
+    }
+
+    #[test]
+    fn test_blocking_lookup_hashes() {
+        let endpoint = mock_server(vec![
+            (503, String::new()),
+            (200, format!("[{}]", file_json(1, 1, Some(1)))),
+        ]);
+
+        let api = crate::FuzzySearch::builder()
+            .endpoint(endpoint)
+            .api_key("test")
+            .retry_policy(RetryPolicy {
+                base_delay: std::time::Duration::from_millis(1),
+                ..Default::default()
+            })
+            .cache(crate::MemoryCache::new(
+                std::num::NonZeroUsize::new(10).unwrap(),
+            ))
+            .build_blocking()
+            .unwrap();
+
+        let files = api.lookup_hashes(&[1], None).unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].site_id, 1);
+
+        assert_eq!(api.lookup_hashes(&[1], None).unwrap().len(), 1);
+        assert!(api.invalidate(&CacheKey::hash(1, None)));
+    }
+
+    #[test]
+    fn test_blocking_unauthorized() {
+        let endpoint = mock_server(vec![(401, String::new())]);
+
+        let api = crate::FuzzySearch::builder()
+            .endpoint(endpoint)
+            .api_key("test")
+            .build_blocking()
+            .unwrap();
+
+        let res = api.lookup_url("https://example.com/image.png");
+        assert!(matches!(res, Err(Error::Unauthorized)));
+    }
+}
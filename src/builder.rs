@@ -0,0 +1,403 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::{
+    cache::ResponseCache, rate_limit::RateLimiter, Cache, CacheTtl, Error, FuzzySearch, MatchType,
+    RateLimit, RetryPolicy,
+};
+
+/// Configure and create a [FuzzySearch] instance.
+///
+/// Created with [FuzzySearch::builder].
+#[derive(Debug)]
+pub struct FuzzySearchBuilder {
+    endpoint: String,
+    api_key: Option<String>,
+    client: Option<reqwest::Client>,
+    #[cfg(feature = "blocking")]
+    blocking_client: Option<reqwest::blocking::Client>,
+    timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    user_agent: Option<String>,
+    proxy: Option<reqwest::Proxy>,
+    headers: HeaderMap,
+    default_distance: Option<i64>,
+    default_match_type: Option<MatchType>,
+    hash_chunk_size: usize,
+    max_concurrent_requests: usize,
+    retry_policy: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    cache: Option<Arc<dyn Cache>>,
+    cache_ttl: CacheTtl,
+}
+
+impl Default for FuzzySearchBuilder {
+    fn default() -> Self {
+        Self {
+            endpoint: FuzzySearch::API_ENDPOINT.to_string(),
+            api_key: None,
+            client: None,
+            #[cfg(feature = "blocking")]
+            blocking_client: None,
+            timeout: None,
+            connect_timeout: None,
+            user_agent: None,
+            proxy: None,
+            headers: HeaderMap::new(),
+            default_distance: None,
+            default_match_type: None,
+            hash_chunk_size: FuzzySearch::MAX_HASHES_PER_REQUEST,
+            max_concurrent_requests: FuzzySearch::DEFAULT_MAX_CONCURRENT_REQUESTS,
+            retry_policy: RetryPolicy::default(),
+            rate_limit: None,
+            cache: None,
+            cache_ttl: CacheTtl::default(),
+        }
+    }
+}
+
+impl FuzzySearchBuilder {
+    /// Use a different API endpoint, such as a self-hosted instance.
+    pub fn endpoint<S: Into<String>>(mut self, endpoint: S) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// The API key to use for requests. This is required.
+    pub fn api_key<S: Into<String>>(mut self, api_key: S) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Use an existing client instead of creating a new one.
+    ///
+    /// The timeout, connect timeout, user agent, and proxy options can't be
+    /// used with a custom client and must be configured on it instead.
+    pub fn client(mut self, client: reqwest::Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Use an existing blocking client instead of creating a new one when
+    /// building with [FuzzySearchBuilder::build_blocking].
+    ///
+    /// The timeout, connect timeout, user agent, and proxy options can't be
+    /// used with a custom client and must be configured on it instead.
+    #[cfg(feature = "blocking")]
+    pub fn blocking_client(mut self, client: reqwest::blocking::Client) -> Self {
+        self.blocking_client = Some(client);
+        self
+    }
+
+    /// Timeout for the entire request, including reading the response.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Timeout for establishing a connection.
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// User agent to send with each request.
+    pub fn user_agent<S: Into<String>>(mut self, user_agent: S) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    /// Send requests through a proxy.
+    pub fn proxy(mut self, proxy: reqwest::Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Add a header to send with each request.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    /// Distance to use for searches when one is not provided.
+    pub fn default_distance(mut self, distance: i64) -> Self {
+        self.default_distance = Some(distance);
+        self
+    }
+
+    /// Match type to use for image searches when one is not provided.
+    pub fn default_match_type(mut self, match_type: MatchType) -> Self {
+        self.default_match_type = Some(match_type);
+        self
+    }
+
+    /// How many hashes to send in each request when looking up hashes.
+    ///
+    /// This should not be larger than [FuzzySearch::MAX_HASHES_PER_REQUEST]
+    /// unless the endpoint allows more hashes per request.
+    pub fn hash_chunk_size(mut self, size: usize) -> Self {
+        self.hash_chunk_size = size;
+        self
+    }
+
+    /// How many requests may be made at once when looking up many hashes.
+    pub fn max_concurrent_requests(mut self, max: usize) -> Self {
+        self.max_concurrent_requests = max;
+        self
+    }
+
+    /// How to retry failed requests.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Limit requests made by the client. Without this, requests are only
+    /// limited by the rate limit headers returned by the API.
+    pub fn rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    /// Cache responses from hash lookups, URL lookups, and image searches.
+    pub fn cache<C: Cache + 'static>(mut self, cache: C) -> Self {
+        self.cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// How long responses from each endpoint should be cached.
+    pub fn cache_ttl(mut self, ttl: CacheTtl) -> Self {
+        self.cache_ttl = ttl;
+        self
+    }
+
+    /// Validate the configuration and create the client.
+    pub fn build(self) -> Result<FuzzySearch, Error> {
+        let (endpoint, api_key) = self.validate()?;
+
+        #[cfg(feature = "blocking")]
+        if self.blocking_client.is_some() {
+            return Err(Error::InvalidConfig(
+                "blocking client cannot be used with an async client",
+            ));
+        }
+
+        let has_client_options = self.has_client_options();
+        let client = match self.client {
+            Some(client) => {
+                if has_client_options {
+                    return Err(Error::InvalidConfig(
+                        "client options cannot be used with a custom client",
+                    ));
+                }
+
+                client
+            }
+            None => {
+                let mut builder = reqwest::Client::builder();
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+
+                builder.build()?
+            }
+        };
+
+        Ok(FuzzySearch {
+            endpoint: endpoint.clone(),
+            api_key,
+            client,
+            headers: Arc::new(self.headers),
+            default_distance: self.default_distance,
+            default_match_type: self.default_match_type,
+            hash_chunk_size: self.hash_chunk_size,
+            max_concurrent_requests: self.max_concurrent_requests,
+            retry_policy: Arc::new(self.retry_policy),
+            rate_limiter: Arc::new(RateLimiter::new(self.rate_limit)),
+            cache: self.cache.map(|cache| ResponseCache {
+                cache,
+                ttl: self.cache_ttl,
+                base_url: endpoint,
+            }),
+        })
+    }
+
+    /// Validate the configuration and create a blocking client.
+    #[cfg(feature = "blocking")]
+    pub fn build_blocking(self) -> Result<crate::blocking::FuzzySearch, Error> {
+        let (endpoint, api_key) = self.validate()?;
+
+        if self.client.is_some() {
+            return Err(Error::InvalidConfig(
+                "async client cannot be used with a blocking client",
+            ));
+        }
+
+        let has_client_options = self.has_client_options();
+        let client = match self.blocking_client {
+            Some(client) => {
+                if has_client_options {
+                    return Err(Error::InvalidConfig(
+                        "client options cannot be used with a custom client",
+                    ));
+                }
+
+                client
+            }
+            None => {
+                let mut builder = reqwest::blocking::Client::builder();
+
+                if let Some(timeout) = self.timeout {
+                    builder = builder.timeout(timeout);
+                }
+                if let Some(timeout) = self.connect_timeout {
+                    builder = builder.connect_timeout(timeout);
+                }
+                if let Some(user_agent) = self.user_agent {
+                    builder = builder.user_agent(user_agent);
+                }
+                if let Some(proxy) = self.proxy {
+                    builder = builder.proxy(proxy);
+                }
+
+                builder.build()?
+            }
+        };
+
+        Ok(crate::blocking::FuzzySearch {
+            endpoint: endpoint.clone(),
+            api_key,
+            client,
+            headers: Arc::new(self.headers),
+            default_distance: self.default_distance,
+            default_match_type: self.default_match_type,
+            hash_chunk_size: self.hash_chunk_size,
+            retry_policy: Arc::new(self.retry_policy),
+            rate_limiter: Arc::new(RateLimiter::new(self.rate_limit)),
+            cache: self.cache.map(|cache| ResponseCache {
+                cache,
+                ttl: self.cache_ttl,
+                base_url: endpoint,
+            }),
+        })
+    }
+
+    /// Check that the required options are present and the endpoint is valid,
+    /// returning the normalized endpoint and API key.
+    fn validate(&self) -> Result<(Arc<str>, Arc<str>), Error> {
+        let api_key = self
+            .api_key
+            .as_deref()
+            .ok_or(Error::InvalidConfig("api key is required"))?;
+
+        let endpoint =
+            reqwest::Url::parse(&self.endpoint).map_err(|err| Error::InvalidEndpoint {
+                endpoint: self.endpoint.clone(),
+                reason: err.to_string(),
+            })?;
+        if !matches!(endpoint.scheme(), "http" | "https") {
+            return Err(Error::InvalidEndpoint {
+                endpoint: self.endpoint.clone(),
+                reason: "scheme must be http or https".to_string(),
+            });
+        }
+
+        if self.hash_chunk_size == 0 {
+            return Err(Error::InvalidConfig("hash chunk size must be at least 1"));
+        }
+        if self.max_concurrent_requests == 0 {
+            return Err(Error::InvalidConfig(
+                "max concurrent requests must be at least 1",
+            ));
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            if rate_limit.requests == 0 {
+                return Err(Error::InvalidConfig(
+                    "rate limit must allow at least 1 request",
+                ));
+            }
+            if rate_limit.period.is_zero() {
+                return Err(Error::InvalidConfig("rate limit period must not be zero"));
+            }
+        }
+
+        Ok((self.endpoint.trim_end_matches('/').into(), api_key.into()))
+    }
+
+    /// If any options were set that only apply when creating a new client.
+    fn has_client_options(&self) -> bool {
+        self.timeout.is_some()
+            || self.connect_timeout.is_some()
+            || self.user_agent.is_some()
+            || self.proxy.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build() {
+        let api = FuzzySearch::builder()
+            .api_key("key")
+            .endpoint("http://localhost:8080/v1/")
+            .timeout(Duration::from_secs(5))
+            .user_agent("test")
+            .build()
+            .unwrap();
+
+        assert_eq!(&*api.endpoint, "http://localhost:8080/v1");
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let res = FuzzySearch::builder().build();
+        assert!(matches!(res, Err(Error::InvalidConfig(_))));
+
+        let res = FuzzySearch::builder()
+            .api_key("key")
+            .endpoint("not a url")
+            .build();
+        assert!(matches!(res, Err(Error::InvalidEndpoint { .. })));
+
+        let res = FuzzySearch::builder()
+            .api_key("key")
+            .endpoint("ftp://example.com")
+            .build();
+        assert!(matches!(res, Err(Error::InvalidEndpoint { .. })));
+
+        let res = FuzzySearch::builder()
+            .api_key("key")
+            .client(reqwest::Client::new())
+            .timeout(Duration::from_secs(5))
+            .build();
+        assert!(matches!(res, Err(Error::InvalidConfig(_))));
+
+        for rate_limit in [
+            RateLimit::per_minute(0),
+            RateLimit {
+                requests: 10,
+                period: Duration::ZERO,
+            },
+        ] {
+            let res = FuzzySearch::builder()
+                .api_key("key")
+                .rate_limit(rate_limit)
+                .build();
+            assert!(matches!(res, Err(Error::InvalidConfig(_))));
+        }
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::{group_by_searched_hash, File, MatchType};
+
+/// A store for responses, consulted before making requests to the API.
+///
+/// Implementations should treat errors as cache misses, as a failing cache
+/// should not prevent lookups from working.
+pub trait Cache: Send + Sync + std::fmt::Debug {
+    /// Get a response from the cache, if it exists and has not expired.
+    fn get(&self, key: &CacheKey) -> Option<Vec<File>>;
+
+    /// Add a response to the cache, valid for the given duration.
+    fn insert(&self, key: CacheKey, files: Vec<File>, ttl: Duration);
+
+    /// Remove a response from the cache, returning if it existed.
+    fn invalidate(&self, key: &CacheKey) -> bool;
+
+    /// Remove all responses from the cache.
+    fn clear(&self);
+
+    /// Get how often the cache was used.
+    fn stats(&self) -> CacheStats;
+}
+
+/// Which endpoint a cached response is from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CacheEndpoint {
+    /// Lookups by hash, cached for each hash individually.
+    Hashes,
+    /// Lookups by image URL.
+    Url,
+    /// Image searches, identified by the SHA256 of the image.
+    Image,
+}
+
+impl CacheEndpoint {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CacheEndpoint::Hashes => "hashes",
+            CacheEndpoint::Url => "url",
+            CacheEndpoint::Image => "image",
+        }
+    }
+}
+
+/// Identifies a cached response.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    /// The base URL of the API the response is from, so clients configured
+    /// with different endpoints can share a cache.
+    pub base_url: String,
+    /// The endpoint the response is from.
+    pub endpoint: CacheEndpoint,
+    /// The hash or URL that was searched.
+    pub query: String,
+    /// The distance used for the search.
+    pub distance: Option<i64>,
+    /// The match type used for the search.
+    pub match_type: Option<MatchType>,
+}
+
+impl CacheKey {
+    /// Key for a lookup of a single hash.
+    pub fn hash(hash: i64, distance: Option<i64>) -> Self {
+        Self {
+            base_url: String::new(),
+            endpoint: CacheEndpoint::Hashes,
+            query: hash.to_string(),
+            distance,
+            match_type: None,
+        }
+    }
+
+    /// Key for an image search.
+    pub fn image(data: &[u8], match_type: MatchType, distance: Option<i64>) -> Self {
+        use sha2::Digest;
+
+        Self {
+            base_url: String::new(),
+            endpoint: CacheEndpoint::Image,
+            query: hex::encode(sha2::Sha256::digest(data)),
+            distance,
+            match_type: Some(match_type),
+        }
+    }
+
+    /// Key for a lookup of an image URL.
+    pub fn url(url: &str) -> Self {
+        Self {
+            base_url: String::new(),
+            endpoint: CacheEndpoint::Url,
+            query: url.to_string(),
+            distance: None,
+            match_type: None,
+        }
+    }
+
+    /// Use the key for responses from the API at the given base URL.
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.base_url = base_url.to_string();
+        self
+    }
+}
+
+impl std::fmt::Display for CacheKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.base_url.is_empty() {
+            write!(f, "{}/", self.base_url)?;
+        }
+        write!(f, "{}:", self.endpoint.as_str())?;
+        if let Some(distance) = self.distance {
+            write!(f, "{}", distance)?;
+        }
+        f.write_str(":")?;
+        if let Some(match_type) = self.match_type {
+            f.write_str(match_type.as_str())?;
+        }
+        write!(f, ":{}", self.query)
+    }
+}
+
+/// How long responses from each endpoint should be cached.
+#[derive(Clone, Copy, Debug)]
+pub struct CacheTtl {
+    /// How long to cache hash lookups.
+    pub hashes: Duration,
+    /// How long to cache URL lookups.
+    pub url: Duration,
+    /// How long to cache image searches.
+    pub image: Duration,
+    /// How long to cache lookups that returned no results, or `None` to not
+    /// cache them at all.
+    pub negative: Option<Duration>,
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        Self {
+            hashes: Duration::from_secs(60 * 60),
+            url: Duration::from_secs(60 * 60),
+            image: Duration::from_secs(60 * 60),
+            negative: Some(Duration::from_secs(5 * 60)),
+        }
+    }
+}
+
+impl CacheTtl {
+    /// How long results should be cached, if at all.
+    fn ttl(&self, endpoint: CacheEndpoint, files: &[File]) -> Option<Duration> {
+        if files.is_empty() {
+            return self.negative;
+        }
+
+        Some(match endpoint {
+            CacheEndpoint::Hashes => self.hashes,
+            CacheEndpoint::Url => self.url,
+            CacheEndpoint::Image => self.image,
+        })
+    }
+}
+
+/// How often the cache was used.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of lookups answered from the cache.
+    pub hits: u64,
+    /// Number of lookups that had to be requested from the API.
+    pub misses: u64,
+    /// Number of entries currently in the cache, including expired entries
+    /// that have not yet been removed.
+    pub entries: usize,
+}
+
+/// An in-memory cache of responses, evicting the least recently used entries
+/// once it reaches capacity.
+#[derive(Debug)]
+pub struct MemoryCache {
+    entries: Mutex<lru::LruCache<CacheKey, (Instant, Vec<File>)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl MemoryCache {
+    /// Create a cache holding up to `capacity` entries.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: Mutex::new(lru::LruCache::new(capacity)),
+            hits: Default::default(),
+            misses: Default::default(),
+        }
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, key: &CacheKey) -> Option<Vec<File>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let files = match entries.get(key) {
+            Some((expires_at, files)) if *expires_at > Instant::now() => Some(files.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        };
+
+        let counter = if files.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        files
+    }
+
+    fn insert(&self, key: CacheKey, files: Vec<File>, ttl: Duration) {
+        let expires_at = Instant::now() + ttl;
+
+        self.entries.lock().unwrap().put(key, (expires_at, files));
+    }
+
+    fn invalidate(&self, key: &CacheKey) -> bool {
+        self.entries.lock().unwrap().pop(key).is_some()
+    }
+
+    fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.entries.lock().unwrap().len(),
+        }
+    }
+}
+
+/// A cache along with how long to keep responses, shared by clients.
+#[derive(Clone, Debug)]
+pub(crate) struct ResponseCache {
+    pub(crate) cache: Arc<dyn Cache>,
+    pub(crate) ttl: CacheTtl,
+    /// Base URL of the client's API, added to every key.
+    pub(crate) base_url: Arc<str>,
+}
+
+impl ResponseCache {
+    /// Get a response from the cache.
+    pub(crate) fn get(&self, key: &CacheKey) -> Option<Vec<File>> {
+        self.cache.get(&key.clone().with_base_url(&self.base_url))
+    }
+
+    /// Add a response to the cache, if it should be cached.
+    pub(crate) fn insert(&self, key: CacheKey, files: Vec<File>) {
+        if let Some(ttl) = self.ttl.ttl(key.endpoint, &files) {
+            self.cache
+                .insert(key.with_base_url(&self.base_url), files, ttl);
+        }
+    }
+
+    /// Remove a response from the cache, returning if it existed.
+    pub(crate) fn invalidate(&self, key: &CacheKey) -> bool {
+        self.cache
+            .invalidate(&key.clone().with_base_url(&self.base_url))
+    }
+
+    /// Split hashes into those with cached results and those that need to be
+    /// requested.
+    pub(crate) fn split_hashes(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> (HashMap<i64, Vec<File>>, Vec<i64>) {
+        let mut cached = HashMap::new();
+        let mut misses = Vec::new();
+
+        for hash in hashes {
+            if cached.contains_key(hash) || misses.contains(hash) {
+                continue;
+            }
+
+            match self.get(&CacheKey::hash(*hash, distance)) {
+                Some(files) => {
+                    cached.insert(*hash, files);
+                }
+                None => misses.push(*hash),
+            }
+        }
+
+        (cached, misses)
+    }
+
+    /// Cache newly requested results for each hash and merge them with the
+    /// cached results, in the order of the hashes.
+    ///
+    /// Only files whose searched hash is known are cached. Any other files are
+    /// returned after the rest, and as they may belong to any of the requested
+    /// hashes, hashes without results are not cached as empty.
+    pub(crate) fn merge_hashes(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+        mut cached: HashMap<i64, Vec<File>>,
+        misses: &[i64],
+        files: Vec<File>,
+    ) -> Vec<File> {
+        let (known, unknown): (Vec<File>, Vec<File>) = files.into_iter().partition(|file| {
+            file.searched_hash
+                .is_some_and(|searched_hash| misses.contains(&searched_hash.0))
+        });
+
+        for (hash, files) in group_by_searched_hash(misses, known) {
+            if !files.is_empty() || unknown.is_empty() {
+                self.insert(CacheKey::hash(hash, distance), files.clone());
+            }
+            cached.insert(hash, files);
+        }
+
+        hashes
+            .iter()
+            .filter_map(|hash| cached.remove(hash))
+            .flatten()
+            .chain(unknown)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_file;
+
+    #[test]
+    fn test_hits_and_misses() {
+        let cache = MemoryCache::new(NonZeroUsize::new(10).unwrap());
+        let key = CacheKey::hash(1, Some(3));
+
+        assert!(cache.get(&key).is_none());
+        cache.insert(
+            key.clone(),
+            vec![test_file(1, None, None)],
+            Duration::from_secs(60),
+        );
+        assert_eq!(cache.get(&key).unwrap()[0].site_id, 1);
+        assert!(cache.get(&CacheKey::hash(1, None)).is_none());
+
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 1,
+                misses: 2,
+                entries: 1
+            }
+        );
+
+        assert!(cache.invalidate(&key));
+        assert!(cache.get(&key).is_none());
+    }
+
+    #[test]
+    fn test_expiry_and_capacity() {
+        let cache = MemoryCache::new(NonZeroUsize::new(2).unwrap());
+        cache.insert(CacheKey::url("a"), vec![], Duration::ZERO);
+        assert!(cache.get(&CacheKey::url("a")).is_none());
+        assert_eq!(cache.stats().entries, 0);
+
+        let ttl = Duration::from_secs(60);
+        cache.insert(CacheKey::url("a"), vec![], ttl);
+        cache.insert(CacheKey::url("b"), vec![], ttl);
+        cache.insert(CacheKey::url("c"), vec![], ttl);
+        assert!(cache.get(&CacheKey::url("a")).is_none());
+        assert!(cache.get(&CacheKey::url("c")).is_some());
+    }
+
+    #[test]
+    fn test_negative_caching() {
+        let cache = ResponseCache {
+            cache: Arc::new(MemoryCache::new(NonZeroUsize::new(10).unwrap())),
+            ttl: CacheTtl {
+                negative: None,
+                ..Default::default()
+            },
+            base_url: "http://localhost".into(),
+        };
+
+        cache.insert(CacheKey::url("a"), vec![]);
+        cache.insert(CacheKey::url("b"), vec![test_file(1, None, None)]);
+        assert!(cache.get(&CacheKey::url("a")).is_none());
+        assert!(cache.get(&CacheKey::url("b")).is_some());
+    }
+
+    #[test]
+    fn test_merge_hashes() {
+        let cache = ResponseCache {
+            cache: Arc::new(MemoryCache::new(NonZeroUsize::new(10).unwrap())),
+            ttl: CacheTtl::default(),
+            base_url: "http://localhost".into(),
+        };
+
+        let files = vec![
+            test_file(1, None, None),
+            test_file(2, None, Some(2)),
+            test_file(3, None, Some(9)),
+        ];
+
+        let merged = cache.merge_hashes(&[1, 2], None, HashMap::new(), &[1, 2], files);
+        assert_eq!(
+            merged.iter().map(|file| file.site_id).collect::<Vec<_>>(),
+            vec![2, 1, 3]
+        );
+
+        assert_eq!(cache.get(&CacheKey::hash(2, None)).unwrap().len(), 1);
+        assert!(cache.get(&CacheKey::hash(1, None)).is_none());
+        assert!(cache.cache.get(&CacheKey::hash(2, None)).is_none());
+    }
+
+    #[test]
+    fn test_key_display() {
+        assert_eq!(CacheKey::hash(-5, Some(3)).to_string(), "hashes:3::-5");
+        assert_eq!(
+            CacheKey::url("a")
+                .with_base_url("https://api.fuzzysearch.net")
+                .to_string(),
+            "https://api.fuzzysearch.net/url:::a"
+        );
+        assert_eq!(
+            CacheKey::image(b"", MatchType::Exact, None).to_string(),
+            "image::exact:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use redb::{ReadableTableMetadata, TableDefinition};
+use serde::{Deserialize, Serialize};
+
+use crate::{Cache, CacheKey, CacheStats, Error, File};
+
+const RESPONSES: TableDefinition<&str, &[u8]> = TableDefinition::new("responses");
+
+/// A response as stored on disk.
+#[derive(Deserialize, Serialize)]
+struct Entry {
+    /// Unix timestamp of when the entry expires.
+    expires_at: i64,
+    files: Vec<File>,
+}
+
+/// A cache of responses persisted to a single file, so it survives restarts.
+///
+/// Expired entries are removed when they are next read, or all at once with
+/// [DiskCache::remove_expired].
+#[derive(Debug)]
+pub struct DiskCache {
+    db: redb::Database,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl DiskCache {
+    /// Open the cache at the given path, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let db = redb::Database::create(path).map_err(redb::Error::from)?;
+
+        // Create the table so reads before the first write succeed.
+        let txn = db.begin_write().map_err(redb::Error::from)?;
+        txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+        txn.commit().map_err(redb::Error::from)?;
+
+        Ok(Self {
+            db,
+            hits: Default::default(),
+            misses: Default::default(),
+        })
+    }
+
+    /// Remove all expired entries from the cache.
+    pub fn remove_expired(&self) -> Result<(), Error> {
+        let now = chrono::Utc::now().timestamp();
+
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        {
+            let mut table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+            table
+                .retain(|_key, value| {
+                    serde_json::from_slice::<Entry>(value)
+                        .map(|entry| entry.expires_at > now)
+                        .unwrap_or(false)
+                })
+                .map_err(redb::Error::from)?;
+        }
+        txn.commit().map_err(redb::Error::from)?;
+
+        Ok(())
+    }
+
+    fn try_get(&self, key: &str) -> Result<Option<Vec<File>>, Error> {
+        let txn = self.db.begin_read().map_err(redb::Error::from)?;
+        let table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+
+        let entry = match table.get(key).map_err(redb::Error::from)? {
+            Some(value) => serde_json::from_slice::<Entry>(value.value()).ok(),
+            None => return Ok(None),
+        };
+
+        match entry {
+            Some(entry) if entry.expires_at > chrono::Utc::now().timestamp() => {
+                Ok(Some(entry.files))
+            }
+            _ => {
+                self.try_remove(key)?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn try_insert(&self, key: &str, files: Vec<File>, ttl: Duration) -> Result<(), Error> {
+        let ttl = chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::MAX);
+        let expires_at = chrono::Utc::now()
+            .checked_add_signed(ttl)
+            .map(|expires_at| expires_at.timestamp())
+            .unwrap_or(i64::MAX);
+
+        let value = serde_json::to_vec(&Entry { expires_at, files })
+            .map_err(|source| Error::Cache(Box::new(source)))?;
+
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        {
+            let mut table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+            table
+                .insert(key, value.as_slice())
+                .map_err(redb::Error::from)?;
+        }
+        txn.commit().map_err(redb::Error::from)?;
+
+        Ok(())
+    }
+
+    fn try_remove(&self, key: &str) -> Result<bool, Error> {
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        let existed = {
+            let mut table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+            let existed = table.remove(key).map_err(redb::Error::from)?.is_some();
+            existed
+        };
+        txn.commit().map_err(redb::Error::from)?;
+
+        Ok(existed)
+    }
+
+    fn try_clear(&self) -> Result<(), Error> {
+        let txn = self.db.begin_write().map_err(redb::Error::from)?;
+        {
+            let mut table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+            table.retain(|_, _| false).map_err(redb::Error::from)?;
+        }
+        txn.commit().map_err(redb::Error::from)?;
+
+        Ok(())
+    }
+
+    fn try_len(&self) -> Result<usize, Error> {
+        let txn = self.db.begin_read().map_err(redb::Error::from)?;
+        let table = txn.open_table(RESPONSES).map_err(redb::Error::from)?;
+
+        Ok(table.len().map_err(redb::Error::from)? as usize)
+    }
+}
+
+impl Cache for DiskCache {
+    fn get(&self, key: &CacheKey) -> Option<Vec<File>> {
+        let files = self.try_get(&key.to_string());
+        if let Err(_err) = &files {
+            #[cfg(feature = "trace")]
+            tracing::warn!("could not read from disk cache: {}", _err);
+        }
+        let files = files.ok().flatten();
+
+        let counter = if files.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        files
+    }
+
+    fn insert(&self, key: CacheKey, files: Vec<File>, ttl: Duration) {
+        if let Err(_err) = self.try_insert(&key.to_string(), files, ttl) {
+            #[cfg(feature = "trace")]
+            tracing::warn!("could not write to disk cache: {}", _err);
+        }
+    }
+
+    fn invalidate(&self, key: &CacheKey) -> bool {
+        self.try_remove(&key.to_string()).unwrap_or(false)
+    }
+
+    fn clear(&self) {
+        if let Err(_err) = self.try_clear() {
+            #[cfg(feature = "trace")]
+            tracing::warn!("could not clear disk cache: {}", _err);
+        }
+    }
+
+    fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.try_len().unwrap_or_default(),
+        }
+    }
+}
+
+impl From<redb::Error> for Error {
+    fn from(err: redb::Error) -> Self {
+        Error::Cache(Box::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempPath;
+    use crate::{FurAffinityFile, SiteInfo};
+
+    #[test]
+    fn test_disk_cache() {
+        let path = TempPath::new("cache");
+
+        let file = File {
+            site_id: 123,
+            sha256: Some(vec![1, 2, 3]),
+            site_info: Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 456 })),
+            ..Default::default()
+        };
+
+        {
+            let cache = DiskCache::open(&path).unwrap();
+            cache.insert(CacheKey::hash(1, None), vec![file], Duration::from_secs(60));
+            cache.insert(CacheKey::hash(2, None), vec![], Duration::ZERO);
+        }
+
+        let cache = DiskCache::open(&path).unwrap();
+        let files = cache.get(&CacheKey::hash(1, None)).unwrap();
+        assert_eq!(files[0].site_id, 123);
+        assert_eq!(files[0].sha256, Some(vec![1, 2, 3]));
+        assert!(matches!(
+            files[0].site_info,
+            Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 456 }))
+        ));
+
+        assert!(cache.get(&CacheKey::hash(2, None)).is_none());
+        assert_eq!(cache.stats().entries, 1);
+
+        assert!(cache.invalidate(&CacheKey::hash(1, None)));
+        assert!(cache.get(&CacheKey::hash(1, None)).is_none());
+    }
+}
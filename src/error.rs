@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// An error message returned by the API.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ApiError {
+    /// Description of what went wrong.
+    pub error: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.error)
+    }
+}
+
+/// Errors that can occur when using FuzzySearch.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The API key was missing or was rejected by the API.
+    #[error("api key was rejected")]
+    Unauthorized,
+    /// Too many requests were made with this API key.
+    #[error("rate limited by api")]
+    RateLimited {
+        /// How long the API asked to wait before making another request.
+        retry_after: Option<Duration>,
+    },
+    /// The API rejected the request with an error message.
+    #[error("api returned {status}: {error}")]
+    Api {
+        /// Status code of the response.
+        status: reqwest::StatusCode,
+        /// The error returned by the API.
+        error: ApiError,
+    },
+    /// The API returned an unexpected status without a readable error.
+    #[error("api returned {status}: {body}")]
+    ServerError {
+        /// Status code of the response.
+        status: reqwest::StatusCode,
+        /// Body of the response, useful for debugging.
+        body: String,
+    },
+    /// The response could not be decoded into the expected type.
+    #[error("could not decode response: {source}")]
+    Decode {
+        /// The underlying JSON error.
+        source: serde_json::Error,
+        /// The raw payload that failed to decode.
+        body: String,
+    },
+    /// The request could not be sent or the response could not be read.
+    #[error("transport error: {0}")]
+    Transport(#[from] reqwest::Error),
+    /// The configured endpoint was not a valid URL.
+    #[error("invalid endpoint {endpoint}: {reason}")]
+    InvalidEndpoint {
+        /// The endpoint that was provided.
+        endpoint: String,
+        /// Why the endpoint was rejected.
+        reason: String,
+    },
+    /// The client was configured with invalid or conflicting options.
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(&'static str),
+    /// The cache could not be opened or used.
+    #[error("cache error: {0}")]
+    Cache(Box<dyn std::error::Error + Send + Sync>),
+    /// A file could not be read or written.
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// A stored index was corrupt or written by an incompatible version.
+    #[cfg(feature = "index_store")]
+    #[error("invalid index: {0}")]
+    InvalidIndex(String),
+    /// The provided image could not be loaded.
+    #[cfg(feature = "local_hash")]
+    #[error("invalid image: {0}")]
+    InvalidImage(#[from] image::ImageError),
+}
+
+impl Error {
+    /// The HTTP status code associated with this error, if any.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            Error::Unauthorized => Some(reqwest::StatusCode::UNAUTHORIZED),
+            Error::RateLimited { .. } => Some(reqwest::StatusCode::TOO_MANY_REQUESTS),
+            Error::Api { status, .. } | Error::ServerError { status, .. } => Some(*status),
+            Error::Transport(err) => err.status(),
+            _ => None,
+        }
+    }
+}
+
+/// Parse the value of a `Retry-After` header, either as a number of seconds or
+/// as an HTTP date.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let delta = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+
+    Some(delta.to_std().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("30"), Some(Duration::from_secs(30)));
+        assert_eq!(parse_retry_after(" 5 "), Some(Duration::from_secs(5)));
+        assert_eq!(
+            parse_retry_after("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
@@ -0,0 +1,225 @@
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+/// Base64 engine accepting input with or without padding.
+const BASE64: base64::engine::GeneralPurpose = base64::engine::GeneralPurpose::new(
+    &base64::alphabet::STANDARD,
+    base64::engine::GeneralPurposeConfig::new()
+        .with_decode_padding_mode(base64::engine::DecodePaddingMode::Indifferent),
+);
+
+/// A 64 bit perceptual hash of an image, as used by FuzzySearch.
+///
+/// Hashes are serialized as numbers, but can be deserialized from numbers or
+/// strings in decimal, hex, or base64.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Hash(pub i64);
+
+/// The string could not be parsed as a hash.
+#[derive(Clone, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("invalid hash: {0}")]
+pub struct ParseHashError(String);
+
+impl Hash {
+    /// Number of bits that differ between two hashes.
+    pub fn distance(&self, other: &Hash) -> u32 {
+        (self.0 ^ other.0).count_ones()
+    }
+
+    /// Bytes of the hash, in the order produced by the hasher.
+    pub fn to_bytes(self) -> [u8; 8] {
+        self.0.to_be_bytes()
+    }
+
+    /// Create a hash from bytes in the order produced by the hasher.
+    pub fn from_bytes(bytes: [u8; 8]) -> Self {
+        Self(i64::from_be_bytes(bytes))
+    }
+
+    /// Format the hash as 16 hex characters.
+    pub fn to_hex(self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    /// Parse a hash from 16 hex characters, optionally prefixed with `0x`.
+    pub fn from_hex(s: &str) -> Result<Self, ParseHashError> {
+        let s = s.strip_prefix("0x").unwrap_or(s);
+
+        let mut bytes = [0u8; 8];
+        hex::decode_to_slice(s, &mut bytes).map_err(|_| ParseHashError(s.to_string()))?;
+
+        Ok(Self::from_bytes(bytes))
+    }
+
+    /// Format the hash as base64, the same as [img_hash::ImageHash::to_base64].
+    pub fn to_base64(self) -> String {
+        BASE64.encode(self.to_bytes())
+    }
+
+    /// Parse a hash from base64, with or without padding.
+    pub fn from_base64(s: &str) -> Result<Self, ParseHashError> {
+        BASE64
+            .decode(s)
+            .ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(Self::from_bytes)
+            .ok_or_else(|| ParseHashError(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for Hash {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for Hash {
+    type Err = ParseHashError;
+
+    /// Parse a hash from a decimal, hex, or base64 string, trying each in
+    /// that order.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if let Ok(hash) = s.parse() {
+            return Ok(Self(hash));
+        }
+
+        Self::from_hex(s)
+            .or_else(|_| Self::from_base64(s))
+            .map_err(|_| ParseHashError(s.to_string()))
+    }
+}
+
+impl From<i64> for Hash {
+    fn from(hash: i64) -> Self {
+        Self(hash)
+    }
+}
+
+impl From<Hash> for i64 {
+    fn from(hash: Hash) -> Self {
+        hash.0
+    }
+}
+
+impl From<[u8; 8]> for Hash {
+    fn from(bytes: [u8; 8]) -> Self {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl From<Hash> for [u8; 8] {
+    fn from(hash: Hash) -> Self {
+        hash.to_bytes()
+    }
+}
+
+#[cfg(feature = "local_hash")]
+impl From<&img_hash::ImageHash<[u8; 8]>> for Hash {
+    fn from(hash: &img_hash::ImageHash<[u8; 8]>) -> Self {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(hash.as_bytes());
+
+        Self::from_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "local_hash")]
+impl From<Hash> for img_hash::ImageHash<[u8; 8]> {
+    fn from(hash: Hash) -> Self {
+        img_hash::ImageHash::from_bytes(&hash.to_bytes()).expect("hash should always be 8 bytes")
+    }
+}
+
+impl Serialize for Hash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_i64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct HashVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for HashVisitor {
+            type Value = Hash;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number or a decimal, hex, or base64 string")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Hash, E> {
+                Ok(Hash(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Hash, E> {
+                i64::try_from(v)
+                    .map(Hash)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Hash, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Hash, E> {
+                v.try_into()
+                    .map(Hash::from_bytes)
+                    .map_err(|_| E::invalid_length(v.len(), &self))
+            }
+        }
+
+        // Binary formats, such as bincode, can't be asked to describe the
+        // value and always contain a number.
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(HashVisitor)
+        } else {
+            deserializer.deserialize_i64(HashVisitor)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_formats() {
+        let hash = Hash(-5);
+        assert_eq!(hash.to_hex(), "fffffffffffffffb");
+        assert_eq!(hash.to_base64(), "//////////s=");
+
+        for s in [
+            "-5",
+            "fffffffffffffffb",
+            "0xfffffffffffffffb",
+            "//////////s=",
+            "//////////s",
+        ] {
+            assert_eq!(s.parse::<Hash>().unwrap(), hash, "{}", s);
+        }
+        assert!("not a hash".parse::<Hash>().is_err());
+
+        assert_eq!(Hash(0b1011).distance(&Hash(0b0001)), 2);
+        assert_eq!(Hash::from(hash.to_bytes()), hash);
+    }
+
+    #[test]
+    fn test_serde() {
+        let hashes: Vec<Hash> =
+            serde_json::from_str(r#"[-5, "-5", "fffffffffffffffb", "//////////s="]"#).unwrap();
+        assert!(hashes.iter().all(|hash| *hash == Hash(-5)));
+
+        assert_eq!(serde_json::to_string(&Hash(-5)).unwrap(), "-5");
+
+        let bytes = bincode::serialize(&Hash(-5)).unwrap();
+        assert_eq!(bincode::deserialize::<Hash>(&bytes).unwrap(), Hash(-5));
+    }
+}
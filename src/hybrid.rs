@@ -0,0 +1,177 @@
+use crate::index::BkTree;
+use crate::{Error, File, FuzzySearch, Hash};
+
+/// Distance the API uses for hash lookups when none is provided.
+const API_DEFAULT_DISTANCE: i64 = 3;
+
+/// Where a result from a [HybridSearch] was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Source {
+    /// The result was found in the local index.
+    Local,
+    /// The result was returned by the API.
+    Remote,
+}
+
+/// A file found by a [HybridSearch].
+#[derive(Clone, Debug)]
+pub struct HybridFile {
+    /// The file that was found.
+    pub file: File,
+    /// Where the file was found.
+    pub source: Source,
+}
+
+/// Searches a local index of files before falling back to the API.
+///
+/// Only hashes without any local matches are requested from the API.
+#[derive(Debug)]
+pub struct HybridSearch {
+    client: FuzzySearch,
+    local: BkTree<File>,
+}
+
+impl HybridSearch {
+    /// Create a searcher with an empty local index.
+    pub fn new(client: FuzzySearch) -> Self {
+        Self::with_index(client, BkTree::new())
+    }
+
+    /// Create a searcher using an existing local index.
+    pub fn with_index(client: FuzzySearch, local: BkTree<File>) -> Self {
+        Self { client, local }
+    }
+
+    /// The client used for requests to the API.
+    pub fn client(&self) -> &FuzzySearch {
+        &self.client
+    }
+
+    /// The local index of files.
+    pub fn local(&self) -> &BkTree<File> {
+        &self.local
+    }
+
+    /// Add a file to the local index.
+    pub fn insert(&mut self, hash: i64, file: File) {
+        self.local.insert(hash, file);
+    }
+
+    /// Attempt to lookup multiple hashes, first in the local index and then
+    /// from the API for any hashes without local matches.
+    ///
+    /// Results are in the same order as the hashes, with local results having
+    /// their hash, distance, and searched hash filled in. The local index and
+    /// the API are searched with the same distance, which defaults to the
+    /// API's default of 3.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub async fn lookup_hashes(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<HybridFile>, Error> {
+        let distance = distance.or(self.client.default_distance);
+        let max_distance = distance.unwrap_or(API_DEFAULT_DISTANCE).clamp(0, 64) as u32;
+
+        let mut groups = Vec::with_capacity(hashes.len());
+        let mut misses = Vec::new();
+
+        for hash in hashes {
+            if groups.iter().any(|(searched, _)| searched == hash) {
+                continue;
+            }
+
+            let files: Vec<HybridFile> = self
+                .local
+                .find_within(*hash, max_distance)
+                .into_iter()
+                .map(|m| HybridFile {
+                    file: File {
+                        hash: Some(Hash(m.hash)),
+                        distance: Some(m.distance as u64),
+                        searched_hash: Some(Hash(*hash)),
+                        ..m.id.clone()
+                    },
+                    source: Source::Local,
+                })
+                .collect();
+
+            if files.is_empty() {
+                misses.push(*hash);
+            }
+            groups.push((*hash, files));
+        }
+
+        #[cfg(feature = "trace")]
+        tracing::debug!(
+            local = groups.len() - misses.len(),
+            remote = misses.len(),
+            "split hashes"
+        );
+
+        if !misses.is_empty() {
+            let remote = self
+                .client
+                .lookup_hashes_grouped(&misses, Some(max_distance as i64))
+                .await?;
+            for (hash, files) in remote {
+                if let Some((_, group)) = groups.iter_mut().find(|(searched, _)| *searched == hash)
+                {
+                    group.extend(files.into_iter().map(|file| HybridFile {
+                        file,
+                        source: Source::Remote,
+                    }));
+                }
+            }
+        }
+
+        Ok(groups.into_iter().flat_map(|(_, files)| files).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{file_json, get_mock_api, mock_server, MockResponse};
+
+    #[tokio::test]
+    async fn test_hybrid_lookup() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(200, format!("[{}]", file_json(2, 100, None))),
+            MockResponse::new(200, "[]"),
+        ])
+        .await;
+
+        let mut search = HybridSearch::new(get_mock_api(endpoint));
+        search.insert(
+            0b1111,
+            File {
+                site_id: 1,
+                ..Default::default()
+            },
+        );
+
+        let files = search
+            .lookup_hashes(&[0b0111, 100, 0b0111], Some(1))
+            .await
+            .unwrap();
+        assert_eq!(files.len(), 2);
+
+        assert_eq!(files[0].source, Source::Local);
+        assert_eq!(files[0].file.site_id, 1);
+        assert_eq!(files[0].file.distance, Some(1));
+        assert_eq!(files[0].file.searched_hash, Some(Hash(0b0111)));
+
+        assert_eq!(files[1].source, Source::Remote);
+        assert_eq!(files[1].file.site_id, 2);
+        assert_eq!(files[1].file.searched_hash, Some(Hash(100)));
+
+        search.lookup_hashes(&[100], None).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("hash=100"));
+        assert!(requests[0].contains("distance=1"));
+        assert!(requests[1].contains("distance=3"));
+    }
+}
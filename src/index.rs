@@ -0,0 +1,282 @@
+//! Local search of hashes by hamming distance, using the same semantics as
+//! the `distance` parameter of the API.
+
+#[cfg(feature = "index_store")]
+mod store;
+
+#[cfg(feature = "index_store")]
+pub use store::IndexStore;
+
+use crate::Hash;
+
+/// Number of bits that differ between two hashes.
+pub fn distance(a: i64, b: i64) -> u32 {
+    Hash(a).distance(&Hash(b))
+}
+
+/// A hash found within the requested distance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match<'a, T> {
+    /// The ID that was inserted with the hash.
+    pub id: &'a T,
+    /// The hash that matched.
+    pub hash: i64,
+    /// Hamming distance between the matched hash and the searched hash.
+    pub distance: u32,
+}
+
+/// A BK-tree of hashes, each with one or more IDs.
+///
+/// Searching for hashes within a small distance only needs to visit a small
+/// part of the tree, making it suitable for large collections of hashes.
+#[derive(Clone, Debug)]
+pub struct BkTree<T> {
+    nodes: Vec<Node<T>>,
+    len: usize,
+}
+
+#[derive(Clone, Debug)]
+struct Node<T> {
+    hash: i64,
+    ids: Vec<T>,
+    /// Children of this node, keyed by their distance to this node.
+    children: Vec<(u32, usize)>,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self {
+            nodes: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T> BkTree<T> {
+    /// Create an empty tree.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of IDs in the tree.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// If the tree has no IDs.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add an ID for a hash. A hash may have any number of IDs.
+    pub fn insert(&mut self, hash: i64, id: T) {
+        self.len += 1;
+
+        if self.nodes.is_empty() {
+            self.nodes.push(Node::new(hash, id));
+            return;
+        }
+
+        let mut current = 0;
+        loop {
+            let node = &self.nodes[current];
+            let dist = distance(node.hash, hash);
+
+            if dist == 0 {
+                self.nodes[current].ids.push(id);
+                return;
+            }
+
+            match node
+                .children
+                .iter()
+                .find(|(child_dist, _)| *child_dist == dist)
+            {
+                Some((_, child)) => current = *child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::new(hash, id));
+                    self.nodes[current].children.push((dist, child));
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Remove an ID from a hash, returning if it existed.
+    ///
+    /// The hash stays in the tree to keep it searchable, but no longer
+    /// returns any matches once all of its IDs are removed.
+    pub fn remove(&mut self, hash: i64, id: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        let index = match self.find_node(hash) {
+            Some(index) => index,
+            None => return false,
+        };
+
+        let ids = &mut self.nodes[index].ids;
+        match ids.iter().position(|existing| existing == id) {
+            Some(pos) => {
+                ids.remove(pos);
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Find all IDs with hashes within the given distance of the hash, sorted
+    /// by distance.
+    pub fn find_within(&self, hash: i64, max_distance: u32) -> Vec<Match<'_, T>> {
+        let mut matches = Vec::new();
+
+        if self.nodes.is_empty() {
+            return matches;
+        }
+
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            let dist = distance(node.hash, hash);
+
+            if dist <= max_distance {
+                matches.extend(node.ids.iter().map(|id| Match {
+                    id,
+                    hash: node.hash,
+                    distance: dist,
+                }));
+            }
+
+            // Only children within the distance range can contain matches,
+            // due to the triangle inequality.
+            let min = dist.saturating_sub(max_distance);
+            let max = dist.saturating_add(max_distance);
+            stack.extend(
+                node.children
+                    .iter()
+                    .filter(|(child_dist, _)| *child_dist >= min && *child_dist <= max)
+                    .map(|(_, child)| *child),
+            );
+        }
+
+        matches.sort_by_key(|m| m.distance);
+        matches
+    }
+
+    /// Iterate over all hashes and IDs in the tree.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, &T)> {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.ids.iter().map(move |id| (node.hash, id)))
+    }
+
+    fn find_node(&self, hash: i64) -> Option<usize> {
+        let mut current = 0;
+
+        loop {
+            let node = self.nodes.get(current)?;
+            let dist = distance(node.hash, hash);
+
+            if dist == 0 {
+                return Some(current);
+            }
+
+            current = node
+                .children
+                .iter()
+                .find(|(child_dist, _)| *child_dist == dist)
+                .map(|(_, child)| *child)?;
+        }
+    }
+}
+
+impl<T> Node<T> {
+    fn new(hash: i64, id: T) -> Self {
+        Self {
+            hash,
+            ids: vec![id],
+            children: Vec::new(),
+        }
+    }
+}
+
+impl<T> Extend<(i64, T)> for BkTree<T> {
+    fn extend<I: IntoIterator<Item = (i64, T)>>(&mut self, iter: I) {
+        for (hash, id) in iter {
+            self.insert(hash, id);
+        }
+    }
+}
+
+impl<T> FromIterator<(i64, T)> for BkTree<T> {
+    fn from_iter<I: IntoIterator<Item = (i64, T)>>(iter: I) -> Self {
+        let mut tree = Self::new();
+        tree.extend(iter);
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_within_matches_brute_force() {
+        let hashes: Vec<i64> = (0..2000).map(|_| fastrand::i64(..)).collect();
+        let mut tree: BkTree<usize> = hashes.iter().copied().zip(0..).collect();
+        // Add some hashes close to existing ones.
+        for (i, hash) in hashes.iter().enumerate().take(100) {
+            tree.insert(hash ^ (1 << (i % 64)), i + hashes.len());
+        }
+
+        for query in hashes.iter().take(50) {
+            for max_distance in [0, 3, 10] {
+                let mut found: Vec<usize> = tree
+                    .find_within(*query, max_distance)
+                    .into_iter()
+                    .map(|m| *m.id)
+                    .collect();
+                found.sort_unstable();
+
+                let mut expected: Vec<usize> = tree
+                    .iter()
+                    .filter(|(hash, _)| distance(*hash, *query) <= max_distance)
+                    .map(|(_, id)| *id)
+                    .collect();
+                expected.sort_unstable();
+
+                assert_eq!(found, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut tree = BkTree::new();
+        tree.insert(0b1111, "a");
+        tree.insert(0b1111, "b");
+        tree.insert(0b0111, "c");
+        assert_eq!(tree.len(), 3);
+
+        let matches = tree.find_within(0b1111, 1);
+        assert_eq!(
+            matches
+                .iter()
+                .map(|m| (*m.id, m.distance))
+                .collect::<Vec<_>>(),
+            vec![("a", 0), ("b", 0), ("c", 1)]
+        );
+
+        assert!(tree.remove(0b1111, &"a"));
+        assert!(!tree.remove(0b1111, &"a"));
+        assert!(!tree.remove(0b0001, &"c"));
+        assert!(tree.remove(0b1111, &"b"));
+        assert_eq!(tree.len(), 1);
+
+        let matches = tree.find_within(0b1111, 1);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(*matches[0].id, "c");
+    }
+}
@@ -0,0 +1,468 @@
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::BkTree;
+use crate::Error;
+
+const SNAPSHOT_MAGIC: &[u8; 4] = b"FZSI";
+const LOG_MAGIC: &[u8; 4] = b"FZSL";
+const VERSION: u32 = 1;
+
+/// Size of the magic, version, and generation at the start of each file.
+const HEADER_LEN: usize = 16;
+
+const TAG_INSERT: u8 = 1;
+const TAG_REMOVE: u8 = 2;
+
+/// A [BkTree] persisted to disk.
+///
+/// The index is stored as a snapshot file, loaded with a memory map, and a
+/// log of changes made since the snapshot was written, kept next to it with
+/// a `.log` suffix. Each change is appended to the log as it is made, and
+/// [IndexStore::compact] writes a new snapshot with the log applied.
+///
+/// Both files start with a magic value, format version, and generation, which
+/// is increased by each compaction. A log is only replayed onto a snapshot of
+/// the same generation, so a log left behind by an interrupted compaction is
+/// discarded instead of being applied twice.
+///
+/// Snapshots then have the number of records. Each record is a little endian
+/// hash followed by the length of the payload and the payload itself, encoded
+/// as JSON so types with flattened or untyped fields, such as [crate::File],
+/// can be stored.
+#[derive(Debug)]
+pub struct IndexStore<T> {
+    path: PathBuf,
+    log: File,
+    generation: u64,
+    tree: BkTree<T>,
+}
+
+impl<T> IndexStore<T>
+where
+    T: Serialize + DeserializeOwned + PartialEq,
+{
+    /// Open the index at the given path, creating it if it does not exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut tree = BkTree::new();
+        let generation = match File::open(&path) {
+            Ok(file) => read_snapshot(&file, &mut tree)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut log = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(log_path(&path))?;
+        let valid_len = replay_log(&log, generation, &mut tree)?;
+
+        // Drop any partially written change, such as from a crash while
+        // appending, so new changes are not written after it.
+        log.set_len(valid_len)?;
+        if valid_len == 0 {
+            log.seek(SeekFrom::Start(0))?;
+            log.write_all(&header(LOG_MAGIC, generation, &[]))?;
+            log.sync_all()?;
+        }
+        log.seek(SeekFrom::End(0))?;
+
+        Ok(Self {
+            path,
+            log,
+            generation,
+            tree,
+        })
+    }
+
+    /// The loaded index.
+    pub fn tree(&self) -> &BkTree<T> {
+        &self.tree
+    }
+
+    /// Add an ID for a hash, recording it in the log.
+    pub fn insert(&mut self, hash: i64, id: T) -> Result<(), Error> {
+        self.append(TAG_INSERT, hash, &id)?;
+        self.tree.insert(hash, id);
+
+        Ok(())
+    }
+
+    /// Remove an ID from a hash, returning if it existed.
+    ///
+    /// The removal is only recorded in the log if the ID existed.
+    pub fn remove(&mut self, hash: i64, id: &T) -> Result<bool, Error> {
+        if !self.tree.remove(hash, id) {
+            return Ok(false);
+        }
+
+        self.append(TAG_REMOVE, hash, id)?;
+
+        Ok(true)
+    }
+
+    /// Write a new snapshot containing every change and clear the log.
+    ///
+    /// The snapshot is written to a temporary file first, so the existing
+    /// snapshot stays intact if this fails. The log is only cleared after the
+    /// new snapshot is in place.
+    pub fn compact(&mut self) -> Result<(), Error> {
+        let generation = self.generation + 1;
+
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&header(
+            SNAPSHOT_MAGIC,
+            generation,
+            &(self.tree.len() as u64).to_le_bytes(),
+        ))?;
+        for (hash, id) in self.tree.iter() {
+            writer.write_all(&record(None, hash, id)?)?;
+        }
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+
+        std::fs::rename(&tmp_path, &self.path)?;
+        sync_parent(&self.path)?;
+
+        // The old log no longer matches the snapshot's generation, so it is
+        // ignored if this is interrupted.
+        self.log.set_len(0)?;
+        self.log.seek(SeekFrom::Start(0))?;
+        self.log.write_all(&header(LOG_MAGIC, generation, &[]))?;
+        self.log.sync_all()?;
+        self.generation = generation;
+
+        Ok(())
+    }
+
+    fn append(&mut self, tag: u8, hash: i64, id: &T) -> Result<(), Error> {
+        self.log.write_all(&record(Some(tag), hash, id)?)?;
+        self.log.sync_data()?;
+
+        Ok(())
+    }
+}
+
+fn log_path(path: &Path) -> PathBuf {
+    let mut log_path = OsString::from(path.as_os_str());
+    log_path.push(".log");
+    log_path.into()
+}
+
+/// Make a rename of the file durable by syncing the directory containing it.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> Result<(), Error> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    File::open(parent)?.sync_all()?;
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> Result<(), Error> {
+    Ok(())
+}
+
+fn header(magic: &[u8; 4], generation: u64, rest: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + rest.len());
+    buf.extend_from_slice(magic);
+    buf.extend_from_slice(&VERSION.to_le_bytes());
+    buf.extend_from_slice(&generation.to_le_bytes());
+    buf.extend_from_slice(rest);
+    buf
+}
+
+/// Encode a record, prefixed with a tag for log entries.
+fn record<T: Serialize>(tag: Option<u8>, hash: i64, id: &T) -> Result<Vec<u8>, Error> {
+    let payload = serde_json::to_vec(id).map_err(|err| Error::InvalidIndex(err.to_string()))?;
+    let payload_len = u32::try_from(payload.len())
+        .map_err(|_| Error::InvalidIndex("payload is too large".to_string()))?;
+
+    let mut buf = Vec::with_capacity(13 + payload.len());
+    buf.extend(tag);
+    buf.extend_from_slice(&hash.to_le_bytes());
+    buf.extend_from_slice(&payload_len.to_le_bytes());
+    buf.extend_from_slice(&payload);
+
+    Ok(buf)
+}
+
+fn map(file: &File) -> Result<Option<memmap2::Mmap>, Error> {
+    if file.metadata()?.len() == 0 {
+        return Ok(None);
+    }
+
+    // Safety: the map is only held while loading, before the store makes any
+    // changes to the file.
+    let mmap = unsafe { memmap2::Mmap::map(file)? };
+
+    Ok(Some(mmap))
+}
+
+/// Check the magic and version at the start of a file, returning its
+/// generation.
+fn check_header(magic: &[u8; 4], data: &mut Reader<'_>) -> Result<u64, Error> {
+    if data.take(4) != Some(magic.as_slice()) {
+        return Err(Error::InvalidIndex("unknown file format".to_string()));
+    }
+
+    match data.u32() {
+        Some(VERSION) => (),
+        Some(version) => {
+            return Err(Error::InvalidIndex(format!(
+                "unsupported version {}",
+                version
+            )))
+        }
+        None => return Err(Error::InvalidIndex("file is truncated".to_string())),
+    }
+
+    data.u64()
+        .ok_or_else(|| Error::InvalidIndex("file is truncated".to_string()))
+}
+
+/// Load the records from a snapshot, returning its generation.
+fn read_snapshot<T: DeserializeOwned>(file: &File, tree: &mut BkTree<T>) -> Result<u64, Error> {
+    let mmap = match map(file)? {
+        Some(mmap) => mmap,
+        None => return Ok(0),
+    };
+    let mut data = Reader(&mmap);
+
+    let generation = check_header(SNAPSHOT_MAGIC, &mut data)?;
+    let count = data
+        .u64()
+        .ok_or_else(|| Error::InvalidIndex("file is truncated".to_string()))?;
+
+    for _ in 0..count {
+        let (hash, id) = data
+            .record()
+            .ok_or_else(|| Error::InvalidIndex("file is truncated".to_string()))??;
+        tree.insert(hash, id);
+    }
+
+    Ok(generation)
+}
+
+/// Apply all complete changes from the log, returning the length of the log
+/// that was read.
+///
+/// A log from a different generation than the snapshot is not applied, and
+/// has a length of 0 so it is replaced.
+fn replay_log<T>(file: &File, generation: u64, tree: &mut BkTree<T>) -> Result<u64, Error>
+where
+    T: DeserializeOwned + PartialEq,
+{
+    let mmap = match map(file)? {
+        Some(mmap) => mmap,
+        None => return Ok(0),
+    };
+    let mut data = Reader(&mmap);
+
+    if check_header(LOG_MAGIC, &mut data)? != generation {
+        return Ok(0);
+    }
+
+    loop {
+        let valid_len = (mmap.len() - data.0.len()) as u64;
+
+        let tag = match data.take(1) {
+            Some(tag) => tag[0],
+            None => return Ok(valid_len),
+        };
+        let (hash, id) = match data.record() {
+            Some(record) => record?,
+            None => return Ok(valid_len),
+        };
+
+        match tag {
+            TAG_INSERT => tree.insert(hash, id),
+            TAG_REMOVE => {
+                tree.remove(hash, &id);
+            }
+            tag => return Err(Error::InvalidIndex(format!("unknown log entry {}", tag))),
+        }
+    }
+}
+
+/// Reads values from the start of a slice, returning `None` if there was not
+/// enough data.
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+
+        let (value, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(value)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)?.try_into().ok().map(u32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Option<u64> {
+        self.take(8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    fn record<T: DeserializeOwned>(&mut self) -> Option<Result<(i64, T), Error>> {
+        let hash = self.u64()? as i64;
+        let len = self.u32()? as usize;
+        let payload = self.take(len)?;
+
+        Some(
+            serde_json::from_slice(payload)
+                .map(|id| (hash, id))
+                .map_err(|err| Error::InvalidIndex(err.to_string())),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempPath;
+
+    #[test]
+    fn test_index_store() {
+        let path = TempPath::new("index");
+
+        {
+            let mut store = IndexStore::open(&path).unwrap();
+            store.insert(1, "a".to_string()).unwrap();
+            store.insert(3, "b".to_string()).unwrap();
+            store.compact().unwrap();
+
+            store.insert(7, "c".to_string()).unwrap();
+            assert!(store.remove(1, &"a".to_string()).unwrap());
+            assert!(!store.remove(1, &"a".to_string()).unwrap());
+        }
+
+        // Simulate a crash while appending a change.
+        OpenOptions::new()
+            .append(true)
+            .open(log_path(&path))
+            .unwrap()
+            .write_all(&[TAG_INSERT, 1, 2])
+            .unwrap();
+
+        let mut store: IndexStore<String> = IndexStore::open(&path).unwrap();
+        let matches = store.tree().find_within(1, 2);
+        assert_eq!(
+            matches.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+            vec!["b", "c"]
+        );
+
+        store.insert(15, "d".to_string()).unwrap();
+        store.compact().unwrap();
+        drop(store);
+
+        let store: IndexStore<String> = IndexStore::open(&path).unwrap();
+        assert_eq!(store.tree().len(), 3);
+    }
+
+    #[test]
+    fn test_file_payload() {
+        use crate::{FurAffinityFile, SiteInfo};
+
+        let path = TempPath::new("file-index");
+
+        let file = |site_id, site_info| crate::File {
+            site_id,
+            site_info,
+            ..Default::default()
+        };
+        let unknown = SiteInfo::Unknown {
+            site: "NewSite".to_string(),
+            site_info: serde_json::json!({"id": 5}),
+        };
+
+        {
+            let mut store = IndexStore::open(&path).unwrap();
+            store
+                .insert(
+                    1,
+                    file(
+                        1,
+                        Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 2 })),
+                    ),
+                )
+                .unwrap();
+            store.insert(1, file(2, Some(unknown.clone()))).unwrap();
+            store.compact().unwrap();
+
+            store.insert(3, file(3, None)).unwrap();
+            assert!(store.remove(1, &file(2, Some(unknown))).unwrap());
+        }
+
+        let store: IndexStore<crate::File> = IndexStore::open(&path).unwrap();
+        let matches = store.tree().find_within(1, 1);
+        assert_eq!(matches.len(), 2);
+        assert!(matches!(
+            matches[0].id.site_info,
+            Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 2 }))
+        ));
+        assert_eq!(matches[1].id.site_id, 3);
+    }
+
+    #[test]
+    fn test_stale_log() {
+        let path = TempPath::new("stale-index");
+
+        {
+            let mut store = IndexStore::open(&path).unwrap();
+            store.insert(1, "a".to_string()).unwrap();
+            store.insert(3, "b".to_string()).unwrap();
+            assert!(store.remove(3, &"b".to_string()).unwrap());
+        }
+
+        // Simulate a crash after the new snapshot was renamed into place but
+        // before the log was cleared.
+        let old_log = std::fs::read(log_path(&path)).unwrap();
+        let mut store: IndexStore<String> = IndexStore::open(&path).unwrap();
+        store.compact().unwrap();
+        drop(store);
+        std::fs::write(log_path(&path), old_log).unwrap();
+
+        let mut store: IndexStore<String> = IndexStore::open(&path).unwrap();
+        assert_eq!(store.tree().len(), 1);
+        assert_eq!(store.tree().find_within(1, 0)[0].id, "a");
+
+        // The stale log was replaced, so new changes are kept.
+        store.insert(7, "c".to_string()).unwrap();
+        drop(store);
+
+        let store: IndexStore<String> = IndexStore::open(&path).unwrap();
+        assert_eq!(store.tree().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let path = TempPath::new("invalid-index");
+        std::fs::write(&path, b"not an index").unwrap();
+
+        let res = IndexStore::<u64>::open(&path);
+        assert!(matches!(res, Err(Error::InvalidIndex(_))));
+    }
+}
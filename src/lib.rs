@@ -1,19 +1,85 @@
 use serde::de::DeserializeOwned;
 use std::collections::HashMap;
+use std::sync::Arc;
 
+pub use builder::FuzzySearchBuilder;
+pub use cache::{Cache, CacheEndpoint, CacheKey, CacheStats, CacheTtl, MemoryCache};
+#[cfg(feature = "disk_cache")]
+pub use disk_cache::DiskCache;
+pub use error::{ApiError, Error};
+pub use hash::{Hash, ParseHashError};
+pub use hybrid::{HybridFile, HybridSearch, Source};
+#[cfg(feature = "local_hash")]
+pub use image::ImageError;
+#[cfg(feature = "local_hash")]
+pub use local_hash::{
+    get_hasher, hash_bytes, hash_directory, hash_frames, hash_image, hash_paths, hash_reader,
+    hash_rgba, HashedPath,
+};
+pub use rate_limit::{Quota, RateLimit};
+pub use retry::RetryPolicy;
+pub use source_url::{SourceId, SourceUrl};
+#[cfg(feature = "local_hash")]
+pub use transform::{hash_transforms, Transform};
 pub use types::*;
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
+mod cache;
+#[cfg(feature = "disk_cache")]
+mod disk_cache;
+mod error;
+mod hash;
+mod hybrid;
+pub mod index;
+#[cfg(feature = "local_hash")]
+mod local_hash;
+mod rate_limit;
+mod retry;
+mod source_url;
+#[cfg(feature = "local_hash")]
+mod transform;
 mod types;
 
 /// FuzzySearch is a collection of methods to get information from fuzzysearch.net.
+///
+/// It is cheap to clone, all clones share the same connection pool and rate
+/// limiter.
+#[derive(Clone)]
 pub struct FuzzySearch {
-    endpoint: String,
-    api_key: String,
+    endpoint: Arc<str>,
+    api_key: Arc<str>,
     client: reqwest::Client,
+    headers: Arc<reqwest::header::HeaderMap>,
+    default_distance: Option<i64>,
+    default_match_type: Option<MatchType>,
+    hash_chunk_size: usize,
+    max_concurrent_requests: usize,
+    retry_policy: Arc<RetryPolicy>,
+    rate_limiter: Arc<rate_limit::RateLimiter>,
+    cache: Option<cache::ResponseCache>,
+}
+
+impl std::fmt::Debug for FuzzySearch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FuzzySearch")
+            .field("endpoint", &self.endpoint)
+            .field("api_key", &"[redacted]")
+            .field("default_distance", &self.default_distance)
+            .field("default_match_type", &self.default_match_type)
+            .field("retry_policy", &self.retry_policy)
+            .field("quota", &self.quota())
+            .field(
+                "cache",
+                &self.cache.as_ref().map(|cache| cache.cache.stats()),
+            )
+            .finish_non_exhaustive()
+    }
 }
 
 /// How to match against FuzzySearch.
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum MatchType {
     /// Start by looking at only exact items, then expand if no results.
     Close,
@@ -23,123 +89,351 @@ pub enum MatchType {
     Force,
 }
 
+impl MatchType {
+    /// The value of the match type used by the API.
+    fn as_str(&self) -> &'static str {
+        match self {
+            MatchType::Close => "close",
+            MatchType::Exact => "exact",
+            MatchType::Force => "force",
+        }
+    }
+}
+
+#[deprecated(since = "0.3.0", note = "use FuzzySearch::builder instead")]
 pub struct FuzzySearchOpts {
     pub endpoint: Option<String>,
     pub client: Option<reqwest::Client>,
     pub api_key: String,
+    /// How to retry failed requests, uses [RetryPolicy::default] if not set.
+    pub retry_policy: Option<RetryPolicy>,
+    /// Limit requests made by the client, only limited by the rate limit
+    /// headers returned by the API if not set. A limit allowing no requests
+    /// is ignored.
+    pub rate_limit: Option<RateLimit>,
 }
 
 impl FuzzySearch {
     pub const API_ENDPOINT: &'static str = "https://api-next.fuzzysearch.net/v1";
+    /// The most hashes the API accepts in a single request.
+    pub const MAX_HASHES_PER_REQUEST: usize = 10;
+    /// How many requests are made at once when looking up many hashes.
+    pub const DEFAULT_MAX_CONCURRENT_REQUESTS: usize = 4;
 
     /// Create a new FuzzySearch instance. Requires the API key.
     pub fn new(api_key: String) -> Self {
-        Self {
-            api_key,
-            client: reqwest::Client::new(),
-            endpoint: Self::API_ENDPOINT.to_string(),
-        }
+        Self::builder()
+            .api_key(api_key)
+            .build()
+            .expect("default options should always be valid")
     }
 
     /// Create a new FuzzySearch instance with a custom client or endpoint.
+    #[deprecated(since = "0.3.0", note = "use FuzzySearch::builder instead")]
+    #[allow(deprecated)]
     pub fn new_with_opts(opts: FuzzySearchOpts) -> Self {
         Self {
-            api_key: opts.api_key,
+            api_key: opts.api_key.into(),
             client: opts.client.unwrap_or_default(),
             endpoint: opts
                 .endpoint
-                .unwrap_or_else(|| Self::API_ENDPOINT.to_string()),
+                .as_deref()
+                .unwrap_or(Self::API_ENDPOINT)
+                .into(),
+            headers: Default::default(),
+            default_distance: None,
+            default_match_type: None,
+            hash_chunk_size: Self::MAX_HASHES_PER_REQUEST,
+            max_concurrent_requests: Self::DEFAULT_MAX_CONCURRENT_REQUESTS,
+            retry_policy: Arc::new(opts.retry_policy.unwrap_or_default()),
+            rate_limiter: Arc::new(rate_limit::RateLimiter::new(opts.rate_limit)),
+            cache: None,
         }
     }
 
+    /// Configure a new FuzzySearch instance.
+    pub fn builder() -> FuzzySearchBuilder {
+        FuzzySearchBuilder::default()
+    }
+
+    /// The remaining quota for the API key in whichever of the API's rate
+    /// limit buckets has the fewest requests left.
+    pub fn quota(&self) -> Quota {
+        self.rate_limiter.quota()
+    }
+
+    /// The response cache, if one was configured.
+    pub fn cache(&self) -> Option<&dyn Cache> {
+        self.cache.as_ref().map(|cache| cache.cache.as_ref())
+    }
+
+    /// Remove a response from the cache, returning if it existed.
+    ///
+    /// The key is used for this client's endpoint, so it does not need a base
+    /// URL.
+    pub fn invalidate(&self, key: &CacheKey) -> bool {
+        self.cache
+            .as_ref()
+            .map(|cache| cache.invalidate(key))
+            .unwrap_or(false)
+    }
+
     /// Makes a request against the API. It deserializes the JSON response.
     /// Generally not used as there are more specific methods available.
     async fn make_request<T: Default + DeserializeOwned>(
         &self,
         endpoint: &str,
         params: &HashMap<&str, String>,
-    ) -> reqwest::Result<T> {
+    ) -> Result<T, Error> {
         let url = format!("{}{}", self.endpoint, endpoint);
 
-        let req = self
-            .client
-            .get(&url)
-            .header("x-api-key", self.api_key.as_bytes())
-            .query(params);
+        self.send(endpoint, || {
+            self.client
+                .get(&url)
+                .headers(self.headers.as_ref().clone())
+                .header("x-api-key", self.api_key.as_bytes())
+                .query(params)
+        })
+        .await
+    }
+
+    /// Sends a request, retrying according to the retry policy.
+    ///
+    /// The request is rebuilt for each attempt as bodies can't be reused.
+    async fn send<T, F>(&self, endpoint: &str, build: F) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+        F: Fn() -> reqwest::RequestBuilder,
+    {
+        let mut attempt = 0;
+
+        loop {
+            attempt += 1;
+
+            self.rate_limiter.acquire(endpoint).await;
+            let req = build().headers(trace_headers());
+
+            let res = match req.send().await {
+                Ok(resp) => self.handle_response(endpoint, resp).await,
+                Err(err) => Err(err.into()),
+            };
+
+            match res {
+                Err(err) if self.retry_policy.should_retry(&err, attempt) => {
+                    let delay = self.retry_policy.delay(&err, attempt);
+
+                    #[cfg(feature = "trace")]
+                    tracing::warn!(attempt, ?delay, "request failed, retrying: {}", err);
 
-        let req = Self::trace_headers(req);
+                    tokio::time::sleep(delay).await;
+                }
+                res => return res,
+            }
+        }
+    }
+
+    /// Reads the body of a response and converts it into the expected type or
+    /// an appropriate error based on the status code.
+    async fn handle_response<T: DeserializeOwned>(
+        &self,
+        endpoint: &str,
+        resp: reqwest::Response,
+    ) -> Result<T, Error> {
+        let status = resp.status();
+        let headers = resp.headers().clone();
+        self.rate_limiter.update(endpoint, status, &headers);
 
-        req.send().await?.json().await
+        let body = resp.bytes().await?;
+
+        parse_response(status, &headers, &body)
     }
 
     /// Attempt to lookup multiple hashes.
+    ///
+    /// Hashes are split into chunks the API accepts and requested concurrently,
+    /// with results returned in the same order as the hashes. If a cache is
+    /// configured, only hashes without cached results are requested.
     #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
     pub async fn lookup_hashes(
         &self,
         hashes: &[i64],
         distance: Option<i64>,
-    ) -> reqwest::Result<Vec<File>> {
+    ) -> Result<Vec<File>, Error> {
+        let distance = distance.or(self.default_distance);
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.fetch_hashes(hashes, distance).await,
+        };
+
+        let (cached, misses) = cache.split_hashes(hashes, distance);
+        let files = self.fetch_hashes(&misses, distance).await?;
+
+        Ok(cache.merge_hashes(hashes, distance, cached, &misses, files))
+    }
+
+    /// Request hashes from the API in chunks.
+    async fn fetch_hashes(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<File>, Error> {
+        use futures_util::stream::{StreamExt, TryStreamExt};
+
+        let chunks: Vec<Vec<File>> =
+            futures_util::stream::iter(hashes.chunks(self.hash_chunk_size))
+                .map(|chunk| self.lookup_hash_chunk(chunk, distance))
+                .buffered(self.max_concurrent_requests)
+                .try_collect()
+                .await?;
+
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    /// Attempt to lookup multiple hashes, returning each submission only once.
+    ///
+    /// This is useful for hashes of many frames of the same animation, which
+    /// are likely to find the same submissions.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub async fn lookup_hashes_deduped(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<File>, Error> {
+        let files = self.lookup_hashes(hashes, distance).await?;
+
+        Ok(dedup_files(files))
+    }
+
+    /// Attempt to lookup the hashes of transformed images, such as those from
+    /// [crate::hash_transforms], returning which transform found each result.
+    ///
+    /// If multiple transforms produced the same hash, results are reported
+    /// for the first of them.
+    #[cfg(feature = "local_hash")]
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub async fn lookup_transforms(
+        &self,
+        hashes: &[(crate::Transform, i64)],
+        distance: Option<i64>,
+    ) -> Result<Vec<(crate::Transform, File)>, Error> {
+        let searched = transform_hashes(hashes);
+        let groups = self.lookup_hashes_grouped(&searched, distance).await?;
+
+        Ok(attribute_transforms(hashes, groups))
+    }
+
+    /// Attempt to lookup multiple hashes, grouping results by the hash that
+    /// found them.
+    ///
+    /// Groups are in the same order as the hashes and every hash has a group,
+    /// even if no results were found for it.
+    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
+    pub async fn lookup_hashes_grouped(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<(i64, Vec<File>)>, Error> {
+        let files = self.lookup_hashes(hashes, distance).await?;
+
+        Ok(group_by_searched_hash(hashes, files))
+    }
+
+    /// Lookup a chunk of hashes that fits in a single request.
+    async fn lookup_hash_chunk(
+        &self,
+        hashes: &[i64],
+        distance: Option<i64>,
+    ) -> Result<Vec<File>, Error> {
         let mut params = HashMap::new();
-        params.insert(
-            "hash",
-            hashes
-                .iter()
-                .map(|hash| hash.to_string())
-                .collect::<Vec<_>>()
-                .join(","),
-        );
+        params.insert("hash", hashes_param(hashes));
         if let Some(distance) = distance {
             params.insert("distance", distance.to_string());
         }
 
-        self.make_request("/hashes", &params).await
+        let mut files: Vec<File> = self.make_request("/hashes", &params).await?;
+        fill_searched_hash(hashes, &mut files);
+
+        Ok(files)
     }
 
     /// Attempt to perform a search using an image URL.
     #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self)))]
-    pub async fn lookup_url(&self, url: &str) -> reqwest::Result<Vec<File>> {
+    pub async fn lookup_url(&self, url: &str) -> Result<Vec<File>, Error> {
+        let key = CacheKey::url(url);
+        if let Some(files) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(files);
+        }
+
         let mut params = HashMap::new();
         params.insert("url", url.to_string());
 
-        self.make_request("/url", &params).await
+        let files: Vec<File> = self.make_request("/url", &params).await?;
+        if let Some(cache) = &self.cache {
+            cache.insert(key, files.clone());
+        }
+
+        Ok(files)
     }
 
     /// Attempt to reverse image search.
     ///
     /// Requiring an exact match will be faster, but potentially leave out results.
-    #[cfg_attr(feature = "trace", tracing::instrument(err, skip(self, data)))]
+    /// If no match type is provided, the default match type is used, falling
+    /// back to [MatchType::Close].
+    #[cfg_attr(
+        feature = "trace",
+        tracing::instrument(err, skip(self, data, exact), fields(exact))
+    )]
     pub async fn image_search(
         &self,
         data: &[u8],
-        exact: MatchType,
+        exact: impl Into<Option<MatchType>>,
         distance: Option<i64>,
-    ) -> reqwest::Result<Vec<File>> {
+    ) -> Result<Vec<File>, Error> {
         use reqwest::multipart::{Form, Part};
 
         let url = format!("{}/image", self.endpoint);
 
-        let part = Part::bytes(Vec::from(data));
-        let form = Form::new().part("image", part);
+        let exact = exact
+            .into()
+            .or(self.default_match_type)
+            .unwrap_or(MatchType::Close);
 
-        let mut query = match exact {
-            MatchType::Exact => vec![("type", "exact".to_string())],
-            MatchType::Force => vec![("type", "force".to_string())],
-            _ => vec![("type", "close".to_string())],
-        };
+        #[cfg(feature = "trace")]
+        tracing::Span::current().record("exact", tracing::field::debug(&exact));
+
+        let distance = distance.or(self.default_distance);
+
+        let key = CacheKey::image(data, exact, distance);
+        if let Some(files) = self.cache.as_ref().and_then(|cache| cache.get(&key)) {
+            return Ok(files);
+        }
+
+        let mut query = vec![("type", exact.as_str().to_string())];
         if let Some(distance) = distance {
             query.push(("distance", distance.to_string()));
         }
 
-        let req = self
-            .client
-            .post(&url)
-            .query(&query)
-            .header("x-api-key", self.api_key.as_bytes())
-            .multipart(form);
+        let files: Vec<File> = self
+            .send("/image", || {
+                let part = Part::bytes(Vec::from(data));
+                let form = Form::new().part("image", part);
+
+                self.client
+                    .post(&url)
+                    .query(&query)
+                    .headers(self.headers.as_ref().clone())
+                    .header("x-api-key", self.api_key.as_bytes())
+                    .multipart(form)
+            })
+            .await?;
 
-        let req = Self::trace_headers(req);
+        if let Some(cache) = &self.cache {
+            cache.insert(key, files.clone());
+        }
 
-        req.send().await?.json().await
+        Ok(files)
     }
 
     /// Attempt to resolve some information from a FurAffinity file.
@@ -147,62 +441,179 @@ impl FuzzySearch {
     pub async fn lookup_furaffinity_file(
         &self,
         url: &str,
-    ) -> reqwest::Result<Vec<FurAffinityFileDetail>> {
+    ) -> Result<Vec<FurAffinityFileDetail>, Error> {
         let mut params = HashMap::new();
         params.insert("search", url.to_string());
 
         self.make_request("/file/furaffinity", &params).await
     }
+}
 
-    #[cfg(feature = "trace")]
-    fn trace_headers(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        use tracing_opentelemetry::OpenTelemetrySpanExt;
+/// Headers to propagate the current trace context to the API.
+#[cfg(feature = "trace")]
+fn trace_headers() -> reqwest::header::HeaderMap {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
 
-        let context = tracing::Span::current().context();
+    let context = tracing::Span::current().context();
 
-        let mut headers: reqwest::header::HeaderMap = Default::default();
-        opentelemetry::global::get_text_map_propagator(|propagator| {
-            propagator.inject_context(
-                &context,
-                &mut opentelemetry_http::HeaderInjector(&mut headers),
-            )
-        });
+    let mut headers: reqwest::header::HeaderMap = Default::default();
+    opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(
+            &context,
+            &mut opentelemetry_http::HeaderInjector(&mut headers),
+        )
+    });
 
-        req.headers(headers)
-    }
+    headers
+}
 
-    #[cfg(not(feature = "trace"))]
-    fn trace_headers(req: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
-        req
+#[cfg(not(feature = "trace"))]
+fn trace_headers() -> reqwest::header::HeaderMap {
+    Default::default()
+}
+
+/// Set the searched hash on results when it can be determined from the hashes
+/// that were requested.
+fn fill_searched_hash(hashes: &[i64], files: &mut [File]) {
+    if let [hash] = hashes {
+        for file in files.iter_mut().filter(|file| file.searched_hash.is_none()) {
+            file.searched_hash = Some(Hash(*hash));
+        }
     }
 }
 
+/// Unique hashes of transformed images, in the order of the transforms.
+///
+/// Transforms often produce the same hash, which only needs to be requested
+/// once.
 #[cfg(feature = "local_hash")]
-pub use image::ImageError;
+fn transform_hashes(hashes: &[(Transform, i64)]) -> Vec<i64> {
+    let mut searched: Vec<i64> = Vec::with_capacity(hashes.len());
+    for (_, hash) in hashes {
+        if !searched.contains(hash) {
+            searched.push(*hash);
+        }
+    }
 
-#[cfg(feature = "local_hash")]
-/// Create an img_hash instance with the same parameters that FuzzySearch uses.
-pub fn get_hasher() -> img_hash::Hasher<[u8; 8]> {
-    img_hash::HasherConfig::with_bytes_type::<[u8; 8]>()
-        .hash_alg(img_hash::HashAlg::Gradient)
-        .hash_size(8, 8)
-        .preproc_dct()
-        .to_hasher()
+    searched
 }
 
+/// Attribute each group of results to the first transform that produced the
+/// hash it was found by.
 #[cfg(feature = "local_hash")]
-/// Hash an image into a 64 bit number that's compatible with FuzzySearch.
-pub fn hash_bytes(b: &[u8]) -> Result<i64, image::ImageError> {
-    let hasher = get_hasher();
+fn attribute_transforms(
+    hashes: &[(Transform, i64)],
+    groups: Vec<(i64, Vec<File>)>,
+) -> Vec<(Transform, File)> {
+    groups
+        .into_iter()
+        .flat_map(|(hash, files)| {
+            let transform = hashes
+                .iter()
+                .find(|(_, searched)| *searched == hash)
+                .map(|(transform, _)| *transform)
+                .expect("groups should only contain searched hashes");
+
+            files.into_iter().map(move |file| (transform, file))
+        })
+        .collect()
+}
+
+/// Group results by the hash that found them, in the order of the hashes.
+///
+/// Results missing a searched hash are assigned to the closest requested hash
+/// by their own hash. Results that can't be assigned to any hash are dropped.
+fn group_by_searched_hash(hashes: &[i64], files: Vec<File>) -> Vec<(i64, Vec<File>)> {
+    let mut groups: Vec<(i64, Vec<File>)> = Vec::with_capacity(hashes.len());
+    for hash in hashes {
+        if !groups.iter().any(|(existing, _)| existing == hash) {
+            groups.push((*hash, Vec::new()));
+        }
+    }
+
+    for mut file in files {
+        let searched_hash = file.searched_hash.map(i64::from).or_else(|| {
+            let hash = file.hash?;
+
+            hashes
+                .iter()
+                .min_by_key(|searched| hash.distance(&Hash(**searched)))
+                .copied()
+        });
 
-    let image = image::load_from_memory(&b)?;
-    let hash = hasher.hash_image(&image);
-    drop(image);
+        let group = searched_hash
+            .and_then(|searched_hash| groups.iter_mut().find(|(hash, _)| *hash == searched_hash));
+
+        if let Some((hash, files)) = group {
+            file.searched_hash = Some(Hash(*hash));
+            files.push(file);
+        }
+    }
 
-    let mut buf = [0u8; 8];
-    buf.copy_from_slice(hash.as_bytes());
+    groups
+}
 
-    Ok(i64::from_be_bytes(buf))
+/// Format hashes into the parameter expected by the API.
+fn hashes_param(hashes: &[i64]) -> String {
+    hashes
+        .iter()
+        .map(|hash| hash.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Convert a response into the expected type.
+///
+/// The status code is checked before attempting to decode the body so that
+/// error pages and error messages are never mistaken for results.
+fn parse_response<T: DeserializeOwned>(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    body: &[u8],
+) -> Result<T, Error> {
+    use reqwest::StatusCode;
+
+    let body_string = || String::from_utf8_lossy(body).into_owned();
+
+    match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => return Err(Error::Unauthorized),
+        StatusCode::TOO_MANY_REQUESTS => {
+            let retry_after = headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(error::parse_retry_after);
+
+            return Err(Error::RateLimited { retry_after });
+        }
+        status if status.is_server_error() => {
+            return Err(Error::ServerError {
+                status,
+                body: body_string(),
+            })
+        }
+        status if !status.is_success() => {
+            return Err(match serde_json::from_slice::<ApiError>(body) {
+                Ok(error) => Error::Api { status, error },
+                Err(_) => Error::ServerError {
+                    status,
+                    body: body_string(),
+                },
+            })
+        }
+        _ => (),
+    }
+
+    serde_json::from_slice(body).map_err(|source| {
+        // Some errors are returned with a successful status code, so check if
+        // the body was an error message before reporting a decoding error.
+        match serde_json::from_slice::<ApiError>(body) {
+            Ok(error) => Error::Api { status, error },
+            Err(_) => Error::Decode {
+                source,
+                body: body_string(),
+            },
+        }
+    })
 }
 
 #[cfg(test)]
@@ -213,7 +624,215 @@ mod tests {
         FuzzySearch::new("eluIOaOhIP1RXlgYetkcZCF8la7p3NoCPy8U0i8dKiT4xdIH".to_string())
     }
 
+    /// A canned response for the mock server.
+    pub(crate) struct MockResponse {
+        status: u16,
+        headers: Vec<(&'static str, &'static str)>,
+        body: String,
+    }
+
+    impl MockResponse {
+        pub(crate) fn new(status: u16, body: impl Into<String>) -> Self {
+            Self {
+                status,
+                headers: vec![],
+                body: body.into(),
+            }
+        }
+
+        pub(crate) fn header(mut self, name: &'static str, value: &'static str) -> Self {
+            self.headers.push((name, value));
+            self
+        }
+    }
+
+    /// Start a server that answers each connection with the next response,
+    /// returning the endpoint and a list of the raw requests it received.
+    pub(crate) async fn mock_server(
+        responses: Vec<MockResponse>,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let received = requests.clone();
+
+        tokio::spawn(async move {
+            for resp in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                loop {
+                    let len = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..len]);
+
+                    let request = String::from_utf8_lossy(&buf);
+                    if let Some(pos) = request.find("\r\n\r\n") {
+                        let content_length = request[..pos]
+                            .lines()
+                            .find_map(|line| line.strip_prefix("content-length: "))
+                            .map(|len| len.parse::<usize>().unwrap())
+                            .unwrap_or(0);
+
+                        if buf.len() >= pos + 4 + content_length || len == 0 {
+                            break;
+                        }
+                    }
+                }
+                received
+                    .lock()
+                    .unwrap()
+                    .push(String::from_utf8_lossy(&buf).into_owned());
+
+                let mut out = format!(
+                    "HTTP/1.1 {} Mock\r\ncontent-length: {}\r\nconnection: close\r\n",
+                    resp.status,
+                    resp.body.len()
+                );
+                for (name, value) in resp.headers {
+                    out.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                out.push_str("\r\n");
+                out.push_str(&resp.body);
+
+                stream.write_all(out.as_bytes()).await.unwrap();
+            }
+        });
+
+        (endpoint, requests)
+    }
+
+    /// A path in the temporary directory, unique to the test process. The
+    /// path and any files next to it with the same name and an extension,
+    /// such as logs, are removed when it is created and dropped.
+    #[cfg(any(
+        feature = "disk_cache",
+        feature = "index_store",
+        feature = "local_hash"
+    ))]
+    pub(crate) struct TempPath(std::path::PathBuf);
+
+    #[cfg(any(
+        feature = "disk_cache",
+        feature = "index_store",
+        feature = "local_hash"
+    ))]
+    impl TempPath {
+        pub(crate) fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("fuzzysearch-{}-{}", name, std::process::id()));
+
+            let temp = Self(path);
+            temp.remove();
+            temp
+        }
+
+        fn remove(&self) {
+            let name = self.0.file_name().unwrap().to_string_lossy().into_owned();
+            let entries = match self.0.parent().map(std::fs::read_dir) {
+                Some(Ok(entries)) => entries,
+                _ => return,
+            };
+
+            for entry in entries.flatten() {
+                let entry_name = entry.file_name().to_string_lossy().into_owned();
+                if entry_name != name && !entry_name.starts_with(&format!("{}.", name)) {
+                    continue;
+                }
+
+                let path = entry.path();
+                let _ = if path.is_dir() {
+                    std::fs::remove_dir_all(path)
+                } else {
+                    std::fs::remove_file(path)
+                };
+            }
+        }
+    }
+
+    #[cfg(any(
+        feature = "disk_cache",
+        feature = "index_store",
+        feature = "local_hash"
+    ))]
+    impl std::ops::Deref for TempPath {
+        type Target = std::path::Path;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    #[cfg(any(
+        feature = "disk_cache",
+        feature = "index_store",
+        feature = "local_hash"
+    ))]
+    impl AsRef<std::path::Path> for TempPath {
+        fn as_ref(&self) -> &std::path::Path {
+            &self.0
+        }
+    }
+
+    #[cfg(any(
+        feature = "disk_cache",
+        feature = "index_store",
+        feature = "local_hash"
+    ))]
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            self.remove();
+        }
+    }
+
+    /// A file with only the fields used by tests filled in.
+    pub(crate) fn test_file(site_id: i64, hash: Option<i64>, searched_hash: Option<i64>) -> File {
+        File {
+            site_id,
+            hash: hash.map(Hash),
+            searched_hash: searched_hash.map(Hash),
+            ..Default::default()
+        }
+    }
+
+    /// JSON for a file as returned by the API, with only the fields used by
+    /// tests filled in.
+    pub(crate) fn file_json(site_id: i64, hash: i64, searched_hash: Option<i64>) -> String {
+        serde_json::json!({
+            "site_id": site_id,
+            "url": "",
+            "filename": "",
+            "artists": null,
+            "rating": null,
+            "posted_at": null,
+            "tags": null,
+            "sha256": null,
+            "hash": hash,
+            "distance": 0,
+            "searched_hash": searched_hash,
+        })
+        .to_string()
+    }
+
+    pub(crate) fn get_mock_api(endpoint: String) -> FuzzySearch {
+        mock_api_builder(endpoint).build().unwrap()
+    }
+
+    pub(crate) fn mock_api_builder(endpoint: String) -> FuzzySearchBuilder {
+        FuzzySearch::builder()
+            .endpoint(endpoint)
+            .api_key("test")
+            .retry_policy(RetryPolicy {
+                base_delay: std::time::Duration::from_millis(1),
+                ..Default::default()
+            })
+    }
+
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_image_search() {
         let api = get_api();
 
@@ -237,6 +856,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_lookup_hashes() {
         let api = get_api();
 
@@ -247,6 +867,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_lookup_url() {
         let api = get_api();
 
@@ -261,6 +882,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[allow(clippy::len_zero)]
     async fn test_lookup_furaffinity_file() {
         let api = get_api();
 
@@ -273,4 +895,309 @@ mod tests {
         assert!(images.is_ok());
         assert!(images.unwrap().len() > 0);
     }
+
+    #[test]
+    fn test_parse_response_errors() {
+        use reqwest::{header::HeaderMap, StatusCode};
+
+        let headers = HeaderMap::new();
+
+        let res = parse_response::<Vec<File>>(StatusCode::UNAUTHORIZED, &headers, b"");
+        assert!(matches!(res, Err(Error::Unauthorized)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, "12".parse().unwrap());
+        let res = parse_response::<Vec<File>>(StatusCode::TOO_MANY_REQUESTS, &headers, b"");
+        assert!(matches!(
+            res,
+            Err(Error::RateLimited { retry_after: Some(retry_after) }) if retry_after.as_secs() == 12
+        ));
+
+        let res = parse_response::<Vec<File>>(StatusCode::BAD_GATEWAY, &headers, b"oops");
+        assert!(matches!(
+            res,
+            Err(Error::ServerError { status: StatusCode::BAD_GATEWAY, body }) if body == "oops"
+        ));
+
+        let res = parse_response::<Vec<File>>(
+            StatusCode::BAD_REQUEST,
+            &headers,
+            br#"{"error": "invalid hash"}"#,
+        );
+        assert!(matches!(
+            res,
+            Err(Error::Api { status: StatusCode::BAD_REQUEST, error }) if error.error == "invalid hash"
+        ));
+
+        let res = parse_response::<Vec<File>>(StatusCode::NOT_FOUND, &headers, b"<html>");
+        assert!(matches!(
+            res,
+            Err(Error::ServerError {
+                status: StatusCode::NOT_FOUND,
+                ..
+            })
+        ));
+
+        let res = parse_response::<Vec<File>>(StatusCode::OK, &headers, br#"{"error": "revoked"}"#);
+        assert!(matches!(res, Err(Error::Api { error, .. }) if error.error == "revoked"));
+
+        let res = parse_response::<Vec<File>>(StatusCode::OK, &headers, b"<html>");
+        assert!(matches!(res, Err(Error::Decode { body, .. }) if body == "<html>"));
+
+        let res = parse_response::<Vec<File>>(StatusCode::OK, &headers, b"[]");
+        assert!(res.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_retry_transient_errors() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(503, "unavailable"),
+            MockResponse::new(429, "").header("retry-after", "0"),
+            MockResponse::new(200, "[]"),
+        ])
+        .await;
+        let api = get_mock_api(endpoint);
+
+        let images = api.lookup_hashes(&[1], None).await;
+        assert!(images.unwrap().is_empty());
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_rebuilds_multipart() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(502, "bad gateway"),
+            MockResponse::new(200, "[]"),
+        ])
+        .await;
+        let api = get_mock_api(endpoint);
+
+        let images = api
+            .image_search(b"image-data", MatchType::Exact, None)
+            .await;
+        assert!(images.unwrap().is_empty());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|req| req.contains("image-data")));
+    }
+
+    #[tokio::test]
+    async fn test_no_retry_unauthorized() {
+        let (endpoint, requests) = mock_server(vec![MockResponse::new(401, "")]).await;
+        let api = get_mock_api(endpoint);
+
+        let images = api.lookup_url("https://example.com/image.png").await;
+        assert!(matches!(images, Err(Error::Unauthorized)));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_quota_from_headers() {
+        let (endpoint, _requests) = mock_server(vec![MockResponse::new(200, "[]")
+            .header("x-rate-limit-total-hash", "60")
+            .header("x-rate-limit-remaining-hash", "59")])
+        .await;
+        let api = get_mock_api(endpoint);
+
+        assert_eq!(api.quota(), Quota::default());
+        api.lookup_hashes(&[1], None).await.unwrap();
+
+        let quota = api.quota();
+        assert_eq!(quota.limit, Some(60));
+        assert_eq!(quota.remaining, Some(59));
+    }
+
+    #[tokio::test]
+    async fn test_builder_defaults() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(200, "[]"),
+            MockResponse::new(200, "[]"),
+        ])
+        .await;
+        let api = mock_api_builder(endpoint)
+            .default_distance(5)
+            .default_match_type(MatchType::Force)
+            .header(
+                reqwest::header::HeaderName::from_static("x-custom"),
+                reqwest::header::HeaderValue::from_static("value"),
+            )
+            .build()
+            .unwrap();
+
+        api.lookup_hashes(&[1], None).await.unwrap();
+        api.image_search(b"image", None, Some(2)).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].contains("distance=5"));
+        assert!(requests[0].contains("x-custom: value"));
+        assert!(requests[1].contains("type=force"));
+        assert!(requests[1].contains("distance=2"));
+    }
+
+    #[tokio::test]
+    async fn test_clone_shares_state() {
+        fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+        assert_shareable::<FuzzySearch>();
+
+        let (endpoint, _requests) = mock_server(vec![MockResponse::new(200, "[]")
+            .header("x-rate-limit-total-hash", "60")
+            .header("x-rate-limit-remaining-hash", "10")])
+        .await;
+        let api = get_mock_api(endpoint);
+        let cloned = api.clone();
+
+        cloned.lookup_hashes(&[1], None).await.unwrap();
+        assert_eq!(api.quota().remaining, Some(10));
+    }
+
+    #[test]
+    fn test_debug_redacts_api_key() {
+        let api = get_api();
+        let debug = format!("{:?}", api);
+
+        assert!(debug.contains("[redacted]"));
+        assert!(!debug.contains("eluIOaOhIP1RXlgYetkcZCF8la7p3NoCPy8U0i8dKiT4xdIH"));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_hashes_chunks() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(200, format!("[{}]", file_json(1, 1, Some(1)))),
+            MockResponse::new(200, format!("[{}]", file_json(2, 3, None))),
+        ])
+        .await;
+        let api = mock_api_builder(endpoint)
+            .hash_chunk_size(2)
+            .max_concurrent_requests(1)
+            .build()
+            .unwrap();
+
+        let files = api.lookup_hashes(&[1, 2, 3], None).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("hash=1%2C2"));
+        assert!(requests[1].contains("hash=3"));
+
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].searched_hash, Some(Hash(1)));
+        assert_eq!(files[1].searched_hash, Some(Hash(3)));
+    }
+
+    #[test]
+    fn test_group_by_searched_hash() {
+        let files = vec![
+            test_file(1, Some(0b1000), Some(0b1000)),
+            test_file(2, Some(0b0111), None),
+            test_file(3, None, Some(0b1000)),
+            test_file(4, None, None),
+            test_file(5, Some(0), Some(42)),
+        ];
+
+        let groups = group_by_searched_hash(&[0b1000, 0b0011, 0b1111, 0b1000], files);
+
+        let groups: Vec<(i64, Vec<i64>)> = groups
+            .into_iter()
+            .map(|(hash, files)| (hash, files.into_iter().map(|file| file.site_id).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![(0b1000, vec![1, 3]), (0b0011, vec![2]), (0b1111, vec![])]
+        );
+    }
+
+    #[cfg(feature = "local_hash")]
+    #[tokio::test]
+    async fn test_lookup_transforms() {
+        let (endpoint, requests) = mock_server(vec![MockResponse::new(
+            200,
+            format!("[{}]", file_json(1, 2, Some(2))),
+        )])
+        .await;
+        let api = get_mock_api(endpoint);
+
+        let hashes = [
+            (Transform::Identity, 1),
+            (Transform::FlipHorizontal, 2),
+            (Transform::Trim, 2),
+        ];
+        let files = api.lookup_transforms(&hashes, None).await.unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].0, Transform::FlipHorizontal);
+        assert_eq!(files[0].1.site_id, 1);
+
+        assert!(requests.lock().unwrap()[0].contains("hash=1%2C2 "));
+    }
+
+    #[tokio::test]
+    async fn test_cached_lookups() {
+        let (endpoint, requests) = mock_server(vec![
+            MockResponse::new(200, format!("[{}]", file_json(1, 1, Some(1)))),
+            MockResponse::new(200, "[]"),
+            MockResponse::new(200, "[]"),
+            MockResponse::new(200, "[]"),
+        ])
+        .await;
+        let api = mock_api_builder(endpoint)
+            .cache(MemoryCache::new(std::num::NonZeroUsize::new(10).unwrap()))
+            .build()
+            .unwrap();
+
+        assert_eq!(api.lookup_hashes(&[1], None).await.unwrap().len(), 1);
+        assert_eq!(api.lookup_hashes(&[1, 2], None).await.unwrap().len(), 1);
+        assert_eq!(api.lookup_hashes(&[2, 1], None).await.unwrap().len(), 1);
+
+        api.lookup_url("https://example.com/a.png").await.unwrap();
+        api.lookup_url("https://example.com/a.png").await.unwrap();
+
+        api.image_search(b"image", MatchType::Exact, None)
+            .await
+            .unwrap();
+        api.image_search(b"image", MatchType::Exact, None)
+            .await
+            .unwrap();
+
+        assert_eq!(requests.lock().unwrap().len(), 4);
+        assert!(requests.lock().unwrap()[1].contains("hash=2 "));
+
+        let stats = api.cache().unwrap().stats();
+        assert_eq!(stats.hits, 5);
+        assert_eq!(stats.misses, 4);
+
+        assert!(api.invalidate(&CacheKey::hash(1, None)));
+        assert!(!api.invalidate(&CacheKey::hash(1, None)));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_hashes_empty() {
+        let api = FuzzySearch::builder()
+            .endpoint("http://127.0.0.1:1")
+            .api_key("test")
+            .build()
+            .unwrap();
+
+        assert!(api.lookup_hashes(&[], None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[allow(deprecated)]
+    async fn test_new_with_opts_empty_rate_limit() {
+        let (endpoint, _requests) = mock_server(vec![MockResponse::new(200, "[]")]).await;
+        let api = FuzzySearch::new_with_opts(FuzzySearchOpts {
+            endpoint: Some(endpoint),
+            client: None,
+            api_key: "test".to_string(),
+            retry_policy: None,
+            rate_limit: Some(RateLimit::per_minute(0)),
+        });
+
+        let res = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            api.lookup_url("https://example.com/a.png"),
+        )
+        .await;
+        assert!(res.unwrap().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,256 @@
+use std::io::{Cursor, Read};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+
+use image::{AnimationDecoder, DynamicImage, ImageFormat};
+use rayon::prelude::*;
+
+use crate::{Error, Hash};
+
+/// Create an img_hash instance with the same parameters that FuzzySearch uses.
+pub fn get_hasher() -> img_hash::Hasher<[u8; 8]> {
+    img_hash::HasherConfig::with_bytes_type::<[u8; 8]>()
+        .hash_alg(img_hash::HashAlg::Gradient)
+        .hash_size(8, 8)
+        .preproc_dct()
+        .to_hasher()
+}
+
+/// Hash an image into a 64 bit number that's compatible with FuzzySearch.
+pub fn hash_bytes(b: &[u8]) -> Result<i64, Error> {
+    let image = image::load_from_memory(b)?;
+
+    Ok(hash_image(&image))
+}
+
+/// Hash an already decoded image.
+pub fn hash_image(image: &DynamicImage) -> i64 {
+    let hash = get_hasher().hash_image(image);
+
+    Hash::from(&hash).into()
+}
+
+/// Hash every `every`th frame of an animated GIF or PNG, starting with the
+/// first frame.
+///
+/// Hashes are returned in frame order with duplicates removed, ready to be
+/// used with [crate::FuzzySearch::lookup_hashes_deduped]. Still images return
+/// only their hash. Animated WebP images can't be decoded and return an error
+/// instead of a hash that may only match the first frame.
+pub fn hash_frames(b: &[u8], every: NonZeroUsize) -> Result<Vec<i64>, Error> {
+    let frames = match image::guess_format(b)? {
+        ImageFormat::Gif => image::codecs::gif::GifDecoder::new(Cursor::new(b))?.into_frames(),
+        ImageFormat::Png => {
+            let decoder = image::codecs::png::PngDecoder::new(Cursor::new(b))?;
+            if !decoder.is_apng() {
+                return Ok(vec![hash_bytes(b)?]);
+            }
+
+            decoder.apng().into_frames()
+        }
+        ImageFormat::WebP if is_animated_webp(b) => {
+            return Err(image::ImageError::Unsupported(
+                image::error::UnsupportedError::from_format_and_kind(
+                    ImageFormat::WebP.into(),
+                    image::error::UnsupportedErrorKind::GenericFeature(
+                        "animated images".to_string(),
+                    ),
+                ),
+            )
+            .into())
+        }
+        _ => return Ok(vec![hash_bytes(b)?]),
+    };
+
+    let hasher = get_hasher();
+    let mut hashes = Vec::new();
+
+    for frame in frames.step_by(every.get()) {
+        let hash = hasher.hash_image(frame?.buffer());
+        let hash = Hash::from(&hash).into();
+
+        if !hashes.contains(&hash) {
+            hashes.push(hash);
+        }
+    }
+
+    Ok(hashes)
+}
+
+/// If a WebP image has the animation flag set in its extended header.
+fn is_animated_webp(b: &[u8]) -> bool {
+    const ANIMATION_FLAG: u8 = 0x02;
+
+    b.get(12..16) == Some(b"VP8X") && b.get(20).is_some_and(|flags| flags & ANIMATION_FLAG != 0)
+}
+
+/// Hash raw RGBA pixels, with 4 bytes per pixel in row-major order.
+pub fn hash_rgba(width: u32, height: u32, data: &[u8]) -> Result<i64, Error> {
+    let image = image::RgbaImage::from_raw(width, height, data.to_vec()).ok_or_else(|| {
+        image::ImageError::Parameter(image::error::ParameterError::from_kind(
+            image::error::ParameterErrorKind::DimensionMismatch,
+        ))
+    })?;
+
+    let hash = get_hasher().hash_image(&image);
+
+    Ok(Hash::from(&hash).into())
+}
+
+/// Hash an encoded image read from a reader, such as a file or response.
+///
+/// The format is detected from the contents of the image.
+pub fn hash_reader<R: Read>(mut reader: R) -> Result<i64, Error> {
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf)?;
+
+    hash_bytes(&buf)
+}
+
+/// The result of hashing a file.
+#[derive(Debug)]
+pub struct HashedPath {
+    /// Path to the file.
+    pub path: PathBuf,
+    /// Hash of the file, or why it could not be hashed.
+    pub hash: Result<i64, Error>,
+}
+
+/// Hash many files in parallel, returning results in the same order as the
+/// paths.
+///
+/// A file that can't be read or decoded does not stop other files from being
+/// hashed; its error is returned with its path instead.
+pub fn hash_paths<I, P>(paths: I) -> Vec<HashedPath>
+where
+    I: IntoIterator<Item = P>,
+    P: Into<PathBuf>,
+{
+    let paths: Vec<PathBuf> = paths.into_iter().map(Into::into).collect();
+
+    paths
+        .into_par_iter()
+        .map(|path| {
+            let hash = std::fs::read(&path)
+                .map_err(Error::from)
+                .and_then(|data| hash_bytes(&data));
+
+            HashedPath { path, hash }
+        })
+        .collect()
+}
+
+/// Hash every image in a directory and its subdirectories in parallel.
+///
+/// Only files with an image extension are hashed, sorted by path. Entries
+/// that can't be read are included with an error.
+pub fn hash_directory<P: AsRef<Path>>(path: P) -> Vec<HashedPath> {
+    let mut paths = Vec::new();
+    let mut errors = Vec::new();
+
+    for entry in walkdir::WalkDir::new(path) {
+        match entry {
+            Ok(entry) if entry.file_type().is_file() => {
+                if image::ImageFormat::from_path(entry.path()).is_ok() {
+                    paths.push(entry.into_path());
+                }
+            }
+            Ok(_) => (),
+            Err(err) => errors.push(HashedPath {
+                path: err.path().map(Path::to_path_buf).unwrap_or_default(),
+                hash: Err(std::io::Error::from(err).into()),
+            }),
+        }
+    }
+
+    let mut hashed = hash_paths(paths);
+    hashed.extend(errors);
+    hashed.sort_by(|a, b| a.path.cmp(&b.path));
+    hashed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::TempPath;
+
+    fn test_image() -> image::RgbaImage {
+        image::RgbaImage::from_fn(32, 32, |x, y| {
+            image::Rgba([(x * 8) as u8, (y * 8) as u8, ((x + y) * 4) as u8, 255])
+        })
+    }
+
+    #[test]
+    fn test_hash_sources_agree() {
+        let image = test_image();
+
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(image.clone())
+            .write_to(&mut png, image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let hash = hash_bytes(&png).unwrap();
+        assert_eq!(hash_reader(png.as_slice()).unwrap(), hash);
+        assert_eq!(hash_image(&DynamicImage::ImageRgba8(image.clone())), hash);
+        assert_eq!(hash_rgba(32, 32, image.as_raw()).unwrap(), hash);
+    }
+
+    #[test]
+    fn test_hash_directory() {
+        let dir = TempPath::new("hash");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+
+        test_image().save(dir.join("nested/a.png")).unwrap();
+        std::fs::write(dir.join("b.png"), b"not an image").unwrap();
+        std::fs::write(dir.join("c.txt"), b"not an image").unwrap();
+
+        let hashed = hash_directory(&dir);
+        assert_eq!(hashed.len(), 2);
+
+        assert_eq!(hashed[0].path, dir.join("b.png"));
+        assert!(matches!(hashed[0].hash, Err(Error::InvalidImage(_))));
+
+        assert_eq!(hashed[1].path, dir.join("nested/a.png"));
+        assert_eq!(
+            *hashed[1].hash.as_ref().unwrap(),
+            hash_rgba(32, 32, test_image().as_raw()).unwrap()
+        );
+
+        let hashed = hash_paths([dir.join("missing.png")]);
+        assert!(matches!(hashed[0].hash, Err(Error::Io(_))));
+    }
+
+    #[test]
+    fn test_hash_frames() {
+        let solid = image::RgbaImage::from_pixel(32, 32, image::Rgba([0, 0, 0, 255]));
+        let flipped = image::imageops::flip_horizontal(&test_image());
+        let frames = vec![test_image(), solid, test_image(), flipped];
+
+        let mut gif = Vec::new();
+        image::codecs::gif::GifEncoder::new(&mut gif)
+            .encode_frames(frames.into_iter().map(image::Frame::new))
+            .unwrap();
+
+        let every = |n| NonZeroUsize::new(n).unwrap();
+
+        let hashes = hash_frames(&gif, every(1)).unwrap();
+        assert_eq!(hashes.len(), 3);
+        assert_eq!(hashes[0], hash_bytes(&gif).unwrap());
+
+        let hashes = hash_frames(&gif, every(2)).unwrap();
+        assert_eq!(hashes.len(), 1);
+
+        let mut webp = b"RIFF\0\0\0\0WEBPVP8X\x0a\0\0\0".to_vec();
+        webp.extend_from_slice(&[0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert!(matches!(
+            hash_frames(&webp, every(1)),
+            Err(Error::InvalidImage(image::ImageError::Unsupported(_)))
+        ));
+    }
+
+    #[test]
+    fn test_hash_rgba_dimension_mismatch() {
+        let res = hash_rgba(32, 32, &[0; 16]);
+        assert!(matches!(res, Err(Error::InvalidImage(_))));
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderMap;
+
+/// Limits how many requests may be made by the client.
+///
+/// Requests are allowed to burst up to the number of requests, then refill
+/// evenly over the period.
+#[derive(Clone, Copy, Debug)]
+pub struct RateLimit {
+    /// Number of requests allowed in each period.
+    pub requests: u32,
+    /// Length of each period.
+    pub period: Duration,
+}
+
+impl RateLimit {
+    /// Allow a number of requests per minute, matching how FuzzySearch
+    /// enforces its limits.
+    pub fn per_minute(requests: u32) -> Self {
+        Self {
+            requests,
+            period: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Remaining quota for the API key, as reported by the API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// Total number of requests allowed in the current period.
+    pub limit: Option<u32>,
+    /// Number of requests remaining in the current period.
+    pub remaining: Option<u32>,
+    /// How long until the quota resets.
+    pub reset_after: Option<Duration>,
+}
+
+/// A token bucket shared between all requests from a client. It adapts to the
+/// rate limit headers returned by the API, tracking each of the API's buckets
+/// separately so exhausting one endpoint's quota does not block the others.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    config: Option<RateLimit>,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    tokens: f64,
+    last_refill: Instant,
+    buckets: HashMap<String, Bucket>,
+}
+
+/// Quota of one of the API's rate limit buckets.
+#[derive(Debug, Default)]
+struct Bucket {
+    blocked_until: Option<Instant>,
+    limit: Option<u32>,
+    remaining: Option<u32>,
+    reset_at: Option<Instant>,
+}
+
+impl RateLimiter {
+    const TOTAL_PREFIX: &'static str = "x-rate-limit-total-";
+    const REMAINING_PREFIX: &'static str = "x-rate-limit-remaining-";
+    const RESET: &'static str = "x-rate-limit-reset";
+
+    /// Create a limiter, ignoring a limit that would never allow a request.
+    pub(crate) fn new(config: Option<RateLimit>) -> Self {
+        let config = config.filter(|config| config.requests > 0 && !config.period.is_zero());
+        let capacity = config.map(|config| config.requests as f64).unwrap_or(0.0);
+
+        Self {
+            config,
+            state: Mutex::new(State {
+                tokens: capacity,
+                last_refill: Instant::now(),
+                buckets: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Name of the bucket the API uses for requests to an endpoint.
+    fn bucket(endpoint: &str) -> &str {
+        match endpoint {
+            "/hashes" => "hash",
+            "/image" => "image",
+            endpoint => endpoint.trim_start_matches('/'),
+        }
+    }
+
+    /// Wait until a request to the endpoint is allowed to be made.
+    pub(crate) async fn acquire(&self, endpoint: &str) {
+        while let Err(wait) = self.try_acquire(endpoint) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Attempt to take a token for a request to the endpoint, returning how
+    /// long to wait before trying again if none are available.
+    pub(crate) fn try_acquire(&self, endpoint: &str) -> Result<(), Duration> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        if let Some(bucket) = state.buckets.get_mut(Self::bucket(endpoint)) {
+            if let Some(blocked_until) = bucket.blocked_until {
+                if blocked_until > now {
+                    return Err(blocked_until - now);
+                }
+
+                bucket.blocked_until = None;
+            }
+
+            // Requests made since the last response have used up the
+            // reported quota, so wait until it resets.
+            if let (Some(0), Some(reset_at)) = (bucket.remaining, bucket.reset_at) {
+                if reset_at > now {
+                    return Err(reset_at - now);
+                }
+
+                bucket.remaining = None;
+                bucket.reset_at = None;
+            }
+        }
+
+        if let Some(config) = self.config {
+            let capacity = config.requests as f64;
+            let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+            let rate = capacity / config.period.as_secs_f64();
+
+            state.tokens = (state.tokens + elapsed * rate).min(capacity);
+            state.last_refill = now;
+
+            if state.tokens < 1.0 {
+                let wait = if rate > 0.0 {
+                    Duration::from_secs_f64((1.0 - state.tokens) / rate)
+                } else {
+                    config.period
+                };
+
+                return Err(wait);
+            }
+
+            state.tokens -= 1.0;
+        }
+
+        if let Some(remaining) = state
+            .buckets
+            .get_mut(Self::bucket(endpoint))
+            .and_then(|bucket| bucket.remaining.as_mut())
+        {
+            *remaining = remaining.saturating_sub(1);
+        }
+
+        Ok(())
+    }
+
+    /// Update the limiter with the rate limit information from a response to
+    /// a request to the endpoint.
+    pub(crate) fn update(&self, endpoint: &str, status: reqwest::StatusCode, headers: &HeaderMap) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        let reset = headers
+            .get(Self::RESET)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+
+        // The API may report the quota of every bucket, not only the one used
+        // by this request.
+        let mut reported: HashMap<&str, (Option<u32>, Option<u32>)> = HashMap::new();
+        for (name, value) in headers {
+            let value = match value.to_str().ok().and_then(|value| value.parse().ok()) {
+                Some(value) => value,
+                None => continue,
+            };
+
+            if let Some(bucket) = name.as_str().strip_prefix(Self::TOTAL_PREFIX) {
+                reported.entry(bucket).or_default().0 = Some(value);
+            } else if let Some(bucket) = name.as_str().strip_prefix(Self::REMAINING_PREFIX) {
+                reported.entry(bucket).or_default().1 = Some(value);
+            }
+        }
+
+        for (name, (limit, remaining)) in reported {
+            let remaining = match remaining {
+                Some(remaining) => remaining,
+                None => continue,
+            };
+
+            let bucket = state.buckets.entry(name.to_string()).or_default();
+            bucket.limit = limit;
+            bucket.remaining = Some(remaining);
+            bucket.reset_at = reset.map(|reset| now + reset);
+
+            if remaining == 0 {
+                let period = self
+                    .config
+                    .map(|config| config.period)
+                    .unwrap_or(Duration::from_secs(60));
+
+                bucket.blocked_until = Some(now + reset.unwrap_or(period));
+            }
+        }
+
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            if let Some(retry_after) = headers
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(crate::error::parse_retry_after)
+            {
+                state
+                    .buckets
+                    .entry(Self::bucket(endpoint).to_string())
+                    .or_default()
+                    .blocked_until = Some(now + retry_after);
+            }
+        }
+    }
+
+    /// The most recently known quota of the most constrained bucket.
+    pub(crate) fn quota(&self) -> Quota {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        state
+            .buckets
+            .values()
+            .filter(|bucket| bucket.remaining.is_some())
+            .min_by_key(|bucket| bucket.remaining)
+            .map(|bucket| Quota {
+                limit: bucket.limit,
+                remaining: bucket.remaining,
+                reset_after: bucket
+                    .reset_at
+                    .map(|reset_at| reset_at.saturating_duration_since(now)),
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let limiter = RateLimiter::new(Some(RateLimit::per_minute(2)));
+
+        assert!(limiter.try_acquire("/hashes").is_ok());
+        assert!(limiter.try_acquire("/hashes").is_ok());
+
+        let wait = limiter.try_acquire("/hashes").unwrap_err();
+        assert!(wait > Duration::from_secs(25) && wait <= Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_unlimited_without_config() {
+        let limiter = RateLimiter::new(None);
+
+        for _ in 0..100 {
+            assert!(limiter.try_acquire("/hashes").is_ok());
+        }
+        assert_eq!(limiter.quota(), Quota::default());
+    }
+
+    #[test]
+    fn test_empty_limit_ignored() {
+        for config in [
+            RateLimit::per_minute(0),
+            RateLimit {
+                requests: 10,
+                period: Duration::ZERO,
+            },
+        ] {
+            let limiter = RateLimiter::new(Some(config));
+            assert!(limiter.try_acquire("/hashes").is_ok());
+        }
+    }
+
+    #[test]
+    fn test_update_from_headers() {
+        let limiter = RateLimiter::new(Some(RateLimit::per_minute(100)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-total-image", "10".parse().unwrap());
+        headers.insert("x-rate-limit-remaining-image", "2".parse().unwrap());
+        headers.insert("x-rate-limit-total-hash", "50".parse().unwrap());
+        headers.insert("x-rate-limit-remaining-hash", "40".parse().unwrap());
+        headers.insert("x-rate-limit-reset", "30".parse().unwrap());
+        limiter.update("/image", reqwest::StatusCode::OK, &headers);
+
+        let quota = limiter.quota();
+        assert_eq!(quota.limit, Some(10));
+        assert_eq!(quota.remaining, Some(2));
+        assert!(quota.reset_after.unwrap() <= Duration::from_secs(30));
+
+        assert!(limiter.try_acquire("/image").is_ok());
+        assert!(limiter.try_acquire("/image").is_ok());
+        assert!(limiter.try_acquire("/image").is_err());
+        assert_eq!(limiter.quota().remaining, Some(0));
+
+        // Other endpoints have their own quota.
+        assert!(limiter.try_acquire("/hashes").is_ok());
+    }
+
+    #[test]
+    fn test_exhausted_quota_blocks() {
+        let limiter = RateLimiter::new(None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-rate-limit-total-hash", "10".parse().unwrap());
+        headers.insert("x-rate-limit-remaining-hash", "0".parse().unwrap());
+        headers.insert("x-rate-limit-reset", "15".parse().unwrap());
+        limiter.update("/hashes", reqwest::StatusCode::OK, &headers);
+
+        let wait = limiter.try_acquire("/hashes").unwrap_err();
+        assert!(wait > Duration::from_secs(10) && wait <= Duration::from_secs(15));
+
+        assert!(limiter.try_acquire("/image").is_ok());
+    }
+}
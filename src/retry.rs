@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::Error;
+
+/// How failed requests should be retried.
+///
+/// Delays grow exponentially from `base_delay` up to `max_delay`, with random
+/// jitter applied so many clients don't retry at the same moment.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first request.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub base_delay: Duration,
+    /// Longest delay between any two attempts. Requests are not retried if
+    /// the API asks for a longer wait through `Retry-After`.
+    pub max_delay: Duration,
+    /// Status codes that should be retried.
+    pub retryable_statuses: Vec<StatusCode>,
+    /// If the delay from a `Retry-After` header should be used instead of the
+    /// calculated delay.
+    pub respect_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(250),
+            max_delay: Duration::from_secs(10),
+            retryable_statuses: vec![
+                StatusCode::TOO_MANY_REQUESTS,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::BAD_GATEWAY,
+                StatusCode::SERVICE_UNAVAILABLE,
+                StatusCode::GATEWAY_TIMEOUT,
+            ],
+            respect_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries requests.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// If a request that failed on the given attempt should be tried again.
+    pub(crate) fn should_retry(&self, err: &Error, attempt: u32) -> bool {
+        if attempt >= self.max_attempts {
+            return false;
+        }
+
+        // Let the caller decide what to do about a wait longer than allowed.
+        if let Error::RateLimited {
+            retry_after: Some(retry_after),
+        } = err
+        {
+            if self.respect_retry_after && *retry_after > self.max_delay {
+                return false;
+            }
+        }
+
+        match err {
+            Error::Transport(err) => err.is_connect() || err.is_timeout() || err.is_request(),
+            err => err
+                .status()
+                .map(|status| self.retryable_statuses.contains(&status))
+                .unwrap_or(false),
+        }
+    }
+
+    /// How long to wait after the given attempt failed.
+    pub(crate) fn delay(&self, err: &Error, attempt: u32) -> Duration {
+        if self.respect_retry_after {
+            if let Error::RateLimited {
+                retry_after: Some(retry_after),
+            } = err
+            {
+                return *retry_after;
+            }
+        }
+
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // Use half of the delay as a minimum and randomize the rest.
+        let half = exp / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_should_retry() {
+        let policy = RetryPolicy::default();
+
+        let err = Error::ServerError {
+            status: StatusCode::BAD_GATEWAY,
+            body: String::new(),
+        };
+        assert!(policy.should_retry(&err, 1));
+        assert!(policy.should_retry(&err, 2));
+        assert!(!policy.should_retry(&err, 3));
+
+        assert!(!policy.should_retry(&Error::Unauthorized, 1));
+        assert!(!RetryPolicy::none().should_retry(&err, 1));
+
+        let rate_limited = |secs| Error::RateLimited {
+            retry_after: Some(Duration::from_secs(secs)),
+        };
+        assert!(policy.should_retry(&rate_limited(5), 1));
+        assert!(!policy.should_retry(&rate_limited(60), 1));
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(300),
+            ..Default::default()
+        };
+
+        let err = Error::ServerError {
+            status: StatusCode::BAD_GATEWAY,
+            body: String::new(),
+        };
+
+        for (attempt, max) in [(1, 100), (2, 200), (3, 300), (10, 300)] {
+            let delay = policy.delay(&err, attempt);
+            assert!(delay >= Duration::from_millis(max / 2));
+            assert!(delay <= Duration::from_millis(max));
+        }
+
+        let err = Error::RateLimited {
+            retry_after: Some(Duration::from_secs(30)),
+        };
+        assert_eq!(policy.delay(&err, 1), Duration::from_secs(30));
+    }
+}
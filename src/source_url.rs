@@ -0,0 +1,184 @@
+use crate::Site;
+
+/// An identifier found in a link to a submission or image.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SourceId {
+    /// The ID of a submission, post, or tweet.
+    Submission(i64),
+    /// The ID of a FurAffinity file as seen in the image URL, which is not
+    /// the same as the submission ID.
+    File(i64),
+    /// The MD5 of an image on e621, as seen in the image URL.
+    Md5(String),
+    /// The media key of an image on Twitter, as seen in the image URL.
+    Media(String),
+}
+
+/// A link to a submission or image on a supported site.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SourceUrl {
+    /// The site the link is for.
+    pub site: Site,
+    /// The identifier found in the link.
+    pub id: SourceId,
+}
+
+impl SourceUrl {
+    /// Parse a link to a submission page or image on a supported site.
+    ///
+    /// Links without a scheme are assumed to be HTTPS. Returns `None` if the
+    /// link is not for a supported site or has no recognizable ID.
+    pub fn parse(url: &str) -> Option<Self> {
+        let url = url.trim();
+
+        let url = match reqwest::Url::parse(url) {
+            Ok(url) if url.has_host() => url,
+            _ => reqwest::Url::parse(&format!("https://{}", url)).ok()?,
+        };
+
+        let host = url.host_str()?;
+        let site = Site::from_host(host)?;
+        let segments: Vec<&str> = url
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let id = match site {
+            Site::FurAffinity => parse_furaffinity(&segments),
+            Site::E621 => parse_e621(&segments),
+            Site::Twitter => parse_twitter(&segments),
+            Site::Weasyl => parse_weasyl(&segments),
+        }?;
+
+        Some(Self { site, id })
+    }
+}
+
+fn parse_furaffinity(segments: &[&str]) -> Option<SourceId> {
+    match segments {
+        ["view" | "full", id, ..] => id.parse().ok().map(SourceId::Submission),
+        ["art", _artist, file_id, ..] => file_id.parse().ok().map(SourceId::File),
+        _ => None,
+    }
+}
+
+fn parse_e621(segments: &[&str]) -> Option<SourceId> {
+    match segments {
+        ["posts", id, ..] | ["post", "show", id, ..] => id.parse().ok().map(SourceId::Submission),
+        ["data", .., filename] => {
+            let md5 = file_stem(filename);
+            let is_md5 = md5.len() == 32 && md5.bytes().all(|b| b.is_ascii_hexdigit());
+
+            is_md5.then(|| SourceId::Md5(md5.to_string()))
+        }
+        _ => None,
+    }
+}
+
+fn parse_twitter(segments: &[&str]) -> Option<SourceId> {
+    match segments {
+        [_, "status", id, ..] | ["i", "web", "status", id, ..] => {
+            id.parse().ok().map(SourceId::Submission)
+        }
+        ["media", filename] => Some(SourceId::Media(file_stem(filename).to_string())),
+        _ => None,
+    }
+}
+
+fn parse_weasyl(segments: &[&str]) -> Option<SourceId> {
+    match segments {
+        ["view" | "submission", id, ..] | [_, "submissions", id, ..] => {
+            id.parse().ok().map(SourceId::Submission)
+        }
+        _ => None,
+    }
+}
+
+/// Remove the extension from a filename.
+fn file_stem(filename: &str) -> &str {
+    filename.split_once('.').map_or(filename, |(stem, _)| stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            (
+                "https://www.furaffinity.net/view/123/",
+                Site::FurAffinity,
+                SourceId::Submission(123),
+            ),
+            (
+                "furaffinity.net/full/123",
+                Site::FurAffinity,
+                SourceId::Submission(123),
+            ),
+            (
+                "https://d.facdn.net/art/user/1600000000/1600000000.user_image.png",
+                Site::FurAffinity,
+                SourceId::File(1600000000),
+            ),
+            (
+                "https://d.furaffinity.net/art/user/1600000000/1600000000.user_image.png",
+                Site::FurAffinity,
+                SourceId::File(1600000000),
+            ),
+            (
+                "https://e621.net/posts/456?q=tag",
+                Site::E621,
+                SourceId::Submission(456),
+            ),
+            (
+                "https://static1.e621.net/data/d4/1d/d41d8cd98f00b204e9800998ecf8427e.png",
+                Site::E621,
+                SourceId::Md5("d41d8cd98f00b204e9800998ecf8427e".to_string()),
+            ),
+            (
+                "https://twitter.com/user/status/789",
+                Site::Twitter,
+                SourceId::Submission(789),
+            ),
+            (
+                "x.com/i/web/status/789",
+                Site::Twitter,
+                SourceId::Submission(789),
+            ),
+            (
+                "https://pbs.twimg.com/media/AbCd123.jpg?name=orig",
+                Site::Twitter,
+                SourceId::Media("AbCd123".to_string()),
+            ),
+            (
+                "https://www.weasyl.com/~user/submissions/321/title",
+                Site::Weasyl,
+                SourceId::Submission(321),
+            ),
+            (
+                "https://cdn.weasyl.com/~user/submissions/321/abcdef/user-title.png",
+                Site::Weasyl,
+                SourceId::Submission(321),
+            ),
+        ];
+
+        for (url, site, id) in cases {
+            assert_eq!(
+                SourceUrl::parse(url),
+                Some(SourceUrl { site, id }),
+                "{}",
+                url
+            );
+        }
+
+        for url in [
+            "https://example.com/view/123",
+            "https://www.furaffinity.net/user/someone/",
+            "https://e621.net/posts",
+            "not a url",
+        ] {
+            assert_eq!(SourceUrl::parse(url), None, "{}", url);
+        }
+    }
+}
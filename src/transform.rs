@@ -0,0 +1,143 @@
+use image::{DynamicImage, GenericImageView};
+
+use crate::hash_image;
+
+/// How much a channel may differ from the border color and still be trimmed.
+const TRIM_TOLERANCE: u8 = 16;
+
+/// A change commonly made to reposted images, which prevents the hash of the
+/// repost from matching the original.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Transform {
+    /// The image as it is.
+    Identity,
+    /// The image mirrored left to right.
+    FlipHorizontal,
+    /// The image rotated 90 degrees clockwise.
+    Rotate90,
+    /// The image rotated 180 degrees.
+    Rotate180,
+    /// The image rotated 270 degrees clockwise.
+    Rotate270,
+    /// The center of the image, keeping this percent of the width and height.
+    CenterCrop(u8),
+    /// The image with a solid colored border removed.
+    Trim,
+}
+
+impl Transform {
+    /// Transforms that cover most reposts: flips, rotations, crops to 90, 80,
+    /// and 70 percent, and trimmed borders.
+    pub const STANDARD: &'static [Transform] = &[
+        Transform::Identity,
+        Transform::FlipHorizontal,
+        Transform::Rotate90,
+        Transform::Rotate180,
+        Transform::Rotate270,
+        Transform::CenterCrop(90),
+        Transform::CenterCrop(80),
+        Transform::CenterCrop(70),
+        Transform::Trim,
+    ];
+
+    /// Apply the transform to an image.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        match self {
+            Transform::Identity => image.clone(),
+            Transform::FlipHorizontal => image.fliph(),
+            Transform::Rotate90 => image.rotate90(),
+            Transform::Rotate180 => image.rotate180(),
+            Transform::Rotate270 => image.rotate270(),
+            Transform::CenterCrop(percent) => {
+                let percent = u32::from(*percent).clamp(1, 100);
+                let (width, height) = image.dimensions();
+
+                let crop_width = (width * percent / 100).max(1);
+                let crop_height = (height * percent / 100).max(1);
+
+                image.crop_imm(
+                    (width - crop_width) / 2,
+                    (height - crop_height) / 2,
+                    crop_width,
+                    crop_height,
+                )
+            }
+            Transform::Trim => trim(image),
+        }
+    }
+}
+
+/// Hash an image after applying each transform.
+pub fn hash_transforms(image: &DynamicImage, transforms: &[Transform]) -> Vec<(Transform, i64)> {
+    transforms
+        .iter()
+        .map(|transform| (*transform, hash_image(&transform.apply(image))))
+        .collect()
+}
+
+/// Remove a border matching the color of the top left pixel.
+fn trim(image: &DynamicImage) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return image.clone();
+    }
+
+    let border = image.get_pixel(0, 0);
+    let is_content = |x, y| {
+        let pixel = image.get_pixel(x, y);
+        pixel
+            .0
+            .iter()
+            .zip(border.0.iter())
+            .any(|(a, b)| a.abs_diff(*b) > TRIM_TOLERANCE)
+    };
+
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    for y in 0..height {
+        for x in 0..width {
+            if is_content(x, y) {
+                let (min_x, min_y, max_x, max_y) = bounds.unwrap_or((x, y, x, y));
+                bounds = Some((min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)));
+            }
+        }
+    }
+
+    match bounds {
+        Some((min_x, min_y, max_x, max_y)) => {
+            image.crop_imm(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1)
+        }
+        None => image.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let image = DynamicImage::new_rgba8(100, 50);
+
+        assert_eq!(Transform::Rotate90.apply(&image).dimensions(), (50, 100));
+        assert_eq!(
+            Transform::CenterCrop(80).apply(&image).dimensions(),
+            (80, 40)
+        );
+        assert_eq!(Transform::Trim.apply(&image).dimensions(), (100, 50));
+    }
+
+    #[test]
+    fn test_trim() {
+        let inner = image::RgbaImage::from_fn(20, 10, |x, y| {
+            image::Rgba([(x * 12) as u8, (y * 24) as u8, 128, 255])
+        });
+        let mut bordered = image::RgbaImage::from_pixel(40, 30, image::Rgba([255; 4]));
+        image::imageops::replace(&mut bordered, &inner, 10, 5);
+
+        let trimmed = Transform::Trim.apply(&DynamicImage::ImageRgba8(bordered));
+        assert_eq!(trimmed.dimensions(), (20, 10));
+
+        let hashes = hash_transforms(&trimmed, &[Transform::Identity]);
+        assert_eq!(hashes[0].1, hash_image(&DynamicImage::ImageRgba8(inner)));
+    }
+}
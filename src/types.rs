@@ -1,18 +1,97 @@
 use serde::{Deserialize, Serialize};
 
+use crate::Hash;
+
 /// Which site a result is from and site-specific information.
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(tag = "site", content = "site_info")]
+///
+/// Sites added to FuzzySearch after this version are decoded as
+/// [SiteInfo::Unknown] instead of failing.
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
 pub enum SiteInfo {
     FurAffinity(FurAffinityFile),
-    #[serde(rename = "e621")]
     E621(E621File),
-    Twitter,
-    Weasyl,
+    Twitter(TwitterFile),
+    Weasyl(WeasylFile),
+    /// A site not known to this version of the library.
+    Unknown {
+        /// Name of the site as returned by the API.
+        site: String,
+        /// Site specific information, as returned by the API.
+        site_info: serde_json::Value,
+    },
+}
+
+impl SiteInfo {
+    /// Name of the site as used by the API.
+    pub fn name(&self) -> &str {
+        match self {
+            SiteInfo::FurAffinity(_) => "FurAffinity",
+            SiteInfo::E621(_) => "e621",
+            SiteInfo::Twitter(_) => "Twitter",
+            SiteInfo::Weasyl(_) => "Weasyl",
+            SiteInfo::Unknown { site, .. } => site,
+        }
+    }
+}
+
+/// Site information as it appears in responses, before checking the site.
+#[derive(Deserialize)]
+struct RawSiteInfo {
+    site: String,
+    #[serde(default)]
+    site_info: serde_json::Value,
+}
+
+impl<'de> Deserialize<'de> for SiteInfo {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+
+        let RawSiteInfo { site, site_info } = RawSiteInfo::deserialize(deserializer)?;
+
+        match site.as_str() {
+            "FurAffinity" => serde_json::from_value(site_info)
+                .map(SiteInfo::FurAffinity)
+                .map_err(D::Error::custom),
+            "e621" => serde_json::from_value(site_info)
+                .map(SiteInfo::E621)
+                .map_err(D::Error::custom),
+            "Twitter" => optional_site_info(site_info)
+                .map(SiteInfo::Twitter)
+                .map_err(D::Error::custom),
+            "Weasyl" => optional_site_info(site_info)
+                .map(SiteInfo::Weasyl)
+                .map_err(D::Error::custom),
+            _ => Ok(SiteInfo::Unknown { site, site_info }),
+        }
+    }
+}
+
+impl Serialize for SiteInfo {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("SiteInfo", 2)?;
+        state.serialize_field("site", self.name())?;
+        match self {
+            SiteInfo::FurAffinity(info) => state.serialize_field("site_info", info)?,
+            SiteInfo::E621(info) => state.serialize_field("site_info", info)?,
+            SiteInfo::Twitter(info) => state.serialize_field("site_info", info)?,
+            SiteInfo::Weasyl(info) => state.serialize_field("site_info", info)?,
+            SiteInfo::Unknown { site_info, .. } => state.serialize_field("site_info", site_info)?,
+        }
+        state.end()
+    }
 }
 
 /// Information about a file from FurAffinity.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct FurAffinityFile {
     /// The ID of the file on FurAffinity as seen in the image URL.
     /// This is not the same as the submission ID.
@@ -20,13 +99,48 @@ pub struct FurAffinityFile {
 }
 
 /// Information about a file from e621.
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct E621File {
     /// A list of sources from e621.
     pub sources: Option<Vec<String>>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// Information about a file from Twitter.
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TwitterFile {
+    /// The ID of the tweet containing the image.
+    pub tweet_id: Option<i64>,
+    /// The ID of the image within the tweet.
+    pub media_id: Option<i64>,
+    /// The handle of the author, as used in links.
+    pub screen_name: Option<String>,
+    /// The display name of the author.
+    pub display_name: Option<String>,
+}
+
+/// Information about a file from Weasyl.
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct WeasylFile {
+    /// The ID of the submission, as seen in the submission URL.
+    pub submission_id: Option<i64>,
+    /// The ID of the image file, which is not the same as the submission ID.
+    pub media_id: Option<i64>,
+}
+
+/// Decode site information that may be missing, using the default if it is.
+fn optional_site_info<T>(site_info: serde_json::Value) -> Result<T, serde_json::Error>
+where
+    T: serde::de::DeserializeOwned + Default,
+{
+    match site_info {
+        serde_json::Value::Null => Ok(T::default()),
+        site_info => serde_json::from_value(site_info),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Rating {
     General,
@@ -35,7 +149,7 @@ pub enum Rating {
 }
 
 /// Information about a matching image.
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
 pub struct File {
     /// The site-specific ID.
     pub site_id: i64,
@@ -55,7 +169,7 @@ pub struct File {
     #[serde(with = "opt_hex_u8")]
     pub sha256: Option<Vec<u8>>,
     /// Hash of the image. Only returned in some endpoints.
-    pub hash: Option<i64>,
+    pub hash: Option<Hash>,
     /// Distance of the image compared to the input. Only returned in some endpoints.
     pub distance: Option<u64>,
     /// Site specific information.
@@ -63,7 +177,7 @@ pub struct File {
     #[serde(flatten)]
     pub site_info: Option<SiteInfo>,
     /// The hash that retreived this result. Only returned in some endpoints.
-    pub searched_hash: Option<i64>,
+    pub searched_hash: Option<Hash>,
 }
 
 mod opt_hex_u8 {
@@ -92,41 +206,218 @@ mod opt_hex_u8 {
     }
 }
 
-impl File {
+/// A site that FuzzySearch indexes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Site {
+    FurAffinity,
+    E621,
+    Twitter,
+    Weasyl,
+}
+
+impl Site {
     /// Get the human readable name of the site.
-    pub fn site_name(&self) -> &'static str {
-        match &self.site_info {
-            Some(SiteInfo::Twitter) => "Twitter",
-            Some(SiteInfo::FurAffinity(_)) => "FurAffinity",
-            Some(SiteInfo::E621(_)) => "e621",
-            Some(SiteInfo::Weasyl) => "Weasyl",
-            _ => unreachable!("Search result was missing SiteInfo"),
+    pub fn name(&self) -> &'static str {
+        match self {
+            Site::FurAffinity => "FurAffinity",
+            Site::E621 => "e621",
+            Site::Twitter => "Twitter",
+            Site::Weasyl => "Weasyl",
         }
     }
 
-    /// Get a link to the image's source page.
-    pub fn url(&self) -> String {
+    /// Determine the site from the host of a URL, such as a direct link to
+    /// an image.
+    pub fn from_url(url: &str) -> Option<Self> {
+        let url = reqwest::Url::parse(url).ok()?;
+
+        Self::from_host(url.host_str()?)
+    }
+
+    /// Determine the site from a host, including any subdomains.
+    pub(crate) fn from_host(host: &str) -> Option<Self> {
+        let is_domain = |domain: &str| {
+            host == domain
+                || host
+                    .strip_suffix(domain)
+                    .is_some_and(|sub| sub.ends_with('.'))
+        };
+
+        if is_domain("furaffinity.net") || is_domain("facdn.net") {
+            Some(Site::FurAffinity)
+        } else if is_domain("e621.net") {
+            Some(Site::E621)
+        } else if is_domain("twitter.com") || is_domain("x.com") || is_domain("twimg.com") {
+            Some(Site::Twitter)
+        } else if is_domain("weasyl.com") {
+            Some(Site::Weasyl)
+        } else {
+            None
+        }
+    }
+}
+
+impl std::fmt::Display for Site {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl File {
+    /// Get the site of the file from its site information, or from its URL if
+    /// that is missing.
+    pub fn site(&self) -> Option<Site> {
         match &self.site_info {
-            Some(SiteInfo::Twitter) => format!(
-                "https://twitter.com/{}/status/{}",
-                self.artists.as_ref().unwrap().iter().next().unwrap(),
-                self.site_id
-            ),
-            Some(SiteInfo::FurAffinity(_)) => {
-                format!("https://www.furaffinity.net/view/{}/", self.site_id)
-            }
-            Some(SiteInfo::E621(_)) => format!("https://e621.net/posts/{}", self.site_id),
-            Some(SiteInfo::Weasyl) => format!("https://www.weasyl.com/view/{}/", self.site_id),
-            _ => unreachable!("Search result was missing SiteInfo"),
+            Some(SiteInfo::FurAffinity(_)) => Some(Site::FurAffinity),
+            Some(SiteInfo::E621(_)) => Some(Site::E621),
+            Some(SiteInfo::Twitter(_)) => Some(Site::Twitter),
+            Some(SiteInfo::Weasyl(_)) => Some(Site::Weasyl),
+            Some(SiteInfo::Unknown { .. }) | None => Site::from_url(&self.url),
         }
     }
 
+    /// Get the human readable name of the site, if it is known.
+    pub fn try_site_name(&self) -> Option<&'static str> {
+        self.site().map(|site| site.name())
+    }
+
+    /// Get the human readable name of the site, or `Unknown` if the site could
+    /// not be determined.
+    pub fn site_name(&self) -> &'static str {
+        self.try_site_name().unwrap_or("Unknown")
+    }
+
+    /// Get a link to the image's source page, if the site is known.
+    pub fn try_url(&self) -> Option<String> {
+        let url = match self.site()? {
+            Site::Twitter => {
+                let info = match &self.site_info {
+                    Some(SiteInfo::Twitter(info)) => Some(info),
+                    _ => None,
+                };
+
+                let tweet_id = info.and_then(|info| info.tweet_id).unwrap_or(self.site_id);
+                let screen_name = info
+                    .and_then(|info| info.screen_name.as_ref())
+                    .or_else(|| self.artists.as_ref().and_then(|artists| artists.first()));
+
+                match screen_name {
+                    Some(screen_name) => {
+                        format!("https://twitter.com/{}/status/{}", screen_name, tweet_id)
+                    }
+                    None => format!("https://twitter.com/i/web/status/{}", tweet_id),
+                }
+            }
+            Site::FurAffinity => format!("https://www.furaffinity.net/view/{}/", self.site_id),
+            Site::E621 => format!("https://e621.net/posts/{}", self.site_id),
+            Site::Weasyl => {
+                let submission_id = match &self.site_info {
+                    Some(SiteInfo::Weasyl(info)) => info.submission_id,
+                    _ => None,
+                };
+
+                format!(
+                    "https://www.weasyl.com/view/{}/",
+                    submission_id.unwrap_or(self.site_id)
+                )
+            }
+        };
+
+        Some(url)
+    }
+
+    /// Get a link to the image's source page, falling back to the direct link
+    /// to the image if the site is not known.
+    pub fn url(&self) -> String {
+        self.try_url().unwrap_or_else(|| self.url.clone())
+    }
+
     /// Generate a unique ID for the submission.
+    ///
+    /// Sites the crate does not know about use the site name from the API.
     pub fn id(&self) -> String {
-        format!("{}-{}", self.site_name(), self.site_id)
+        let site = match &self.site_info {
+            Some(info) => info.name(),
+            None => self.site_name(),
+        };
+
+        format!("{}-{}", site, self.site_id)
+    }
+
+    /// Distance between the hash of this file and another hash, if the hash
+    /// of this file is known.
+    pub fn distance_to<H: Into<Hash>>(&self, hash: H) -> Option<u64> {
+        let hash = hash.into();
+
+        self.hash.map(|own| own.distance(&hash) as u64)
     }
 }
 
+/// Recalculate the distance of each file to a locally computed hash, so
+/// results from any endpoint can be ranked the same way.
+///
+/// Files with a hash have their distance replaced with the distance to the
+/// given hash, while files without one keep the distance from the API, if
+/// any. Files are sorted by distance with unknown distances last. If a
+/// maximum distance is given, files further away or with an unknown distance
+/// are removed.
+pub fn verify_distances<H: Into<Hash>>(
+    files: Vec<File>,
+    hash: H,
+    max_distance: Option<u64>,
+) -> Vec<File> {
+    let hash = hash.into();
+
+    let mut files: Vec<File> = files
+        .into_iter()
+        .map(|mut file| {
+            if let Some(distance) = file.distance_to(hash) {
+                file.distance = Some(distance);
+            }
+
+            file
+        })
+        .filter(|file| match (max_distance, file.distance) {
+            (Some(max_distance), Some(distance)) => distance <= max_distance,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .collect();
+
+    files.sort_by_key(|file| file.distance.unwrap_or(u64::MAX));
+    files
+}
+
+/// Remove duplicate results for the same submission, such as when searching
+/// for many frames of an animation.
+///
+/// Results are kept in the order they were first found, using the smallest
+/// distance found for each submission.
+pub fn dedup_files(files: Vec<File>) -> Vec<File> {
+    let mut deduped: Vec<File> = Vec::with_capacity(files.len());
+
+    for file in files {
+        let site = file.site_info.as_ref().map(SiteInfo::name);
+
+        let existing = deduped.iter_mut().find(|existing| {
+            existing.site_id == file.site_id
+                && existing.site_info.as_ref().map(SiteInfo::name) == site
+        });
+
+        match existing {
+            Some(existing) => {
+                if file.distance.unwrap_or(u64::MAX) < existing.distance.unwrap_or(u64::MAX) {
+                    *existing = file;
+                }
+            }
+            None => deduped.push(file),
+        }
+    }
+
+    deduped
+}
+
 /// Information about a matching FurAffinity file.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct FurAffinityFileDetail {
@@ -135,7 +426,7 @@ pub struct FurAffinityFileDetail {
     pub file_id: Option<i32>,
     pub file_size: Option<i32>,
     pub filename: Option<String>,
-    pub hash: Option<i64>,
+    pub hash: Option<Hash>,
     pub hash_str: Option<String>,
     pub id: i32,
     pub posted_at: Option<chrono::DateTime<chrono::Utc>>,
@@ -147,11 +438,246 @@ pub struct FurAffinityFileDetail {
     pub url: Option<String>,
 }
 
+impl FurAffinityFileDetail {
+    /// Get a link to the submission page.
+    pub fn page_url(&self) -> String {
+        format!("https://www.furaffinity.net/view/{}/", self.id)
+    }
+
+    /// Hash of the image, parsed from the hash string if the numeric hash is
+    /// missing.
+    pub fn hash(&self) -> Option<Hash> {
+        self.hash
+            .or_else(|| self.hash_str.as_deref().and_then(|s| s.parse().ok()))
+    }
+
+    /// If the submission has not been deleted from FurAffinity.
+    pub fn is_available(&self) -> bool {
+        !self.deleted
+    }
+}
+
+impl From<FurAffinityFileDetail> for File {
+    /// Convert to a file like those returned by searches.
+    ///
+    /// The site information is only set if the file ID is known. If the
+    /// image URL is also missing, the submission page is used instead so the
+    /// site can still be determined.
+    fn from(detail: FurAffinityFileDetail) -> Self {
+        let page_url = detail.page_url();
+
+        Self {
+            site_id: detail.id as i64,
+            hash: detail.hash(),
+            url: detail.url.unwrap_or(page_url),
+            filename: detail.filename.unwrap_or_default(),
+            artists: detail.artist.map(|artist| vec![artist]),
+            rating: detail.rating,
+            posted_at: detail.posted_at,
+            tags: Some(detail.tags),
+            sha256: detail.sha256,
+            distance: None,
+            site_info: detail
+                .file_id
+                .map(|file_id| SiteInfo::FurAffinity(FurAffinityFile { file_id })),
+            searched_hash: None,
+        }
+    }
+}
+
 /// Container for multiple matches. Includes the hash of the image sent.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Matches {
     /// Hash of the sent image.
-    pub hash: i64,
+    pub hash: Hash,
     /// A list of potential matches.
     pub matches: Vec<File>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::test_file;
+
+    #[test]
+    fn test_verify_distances() {
+        let files = vec![
+            File {
+                distance: Some(0),
+                ..test_file(1, Some(0b1111), None)
+            },
+            File {
+                distance: Some(1),
+                ..test_file(2, None, None)
+            },
+            test_file(3, None, None),
+            test_file(4, Some(0b0001), None),
+        ];
+
+        let verified = verify_distances(files.clone(), 0b0001, None);
+        let ranked: Vec<_> = verified
+            .iter()
+            .map(|file| (file.site_id, file.distance))
+            .collect();
+        assert_eq!(
+            ranked,
+            vec![(4, Some(0)), (2, Some(1)), (1, Some(3)), (3, None)]
+        );
+
+        let verified = verify_distances(files, 0b0001, Some(1));
+        let ids: Vec<_> = verified.iter().map(|file| file.site_id).collect();
+        assert_eq!(ids, vec![4, 2]);
+    }
+
+    #[test]
+    fn test_site() {
+        let file = File {
+            site_id: 123,
+            url: "https://d.furaffinity.net/art/user/1/1.user_image.png".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(file.site(), Some(Site::FurAffinity));
+        assert_eq!(file.url(), "https://www.furaffinity.net/view/123/");
+
+        let file = File {
+            site_id: 123,
+            site_info: Some(SiteInfo::Twitter(Default::default())),
+            ..Default::default()
+        };
+        assert_eq!(file.site_name(), "Twitter");
+        assert_eq!(file.url(), "https://twitter.com/i/web/status/123");
+
+        let file = File {
+            site_id: 123,
+            artists: Some(vec!["Display Name".to_string()]),
+            site_info: Some(SiteInfo::Twitter(TwitterFile {
+                tweet_id: Some(456),
+                screen_name: Some("handle".to_string()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        };
+        assert_eq!(file.url(), "https://twitter.com/handle/status/456");
+
+        let file = File {
+            site_id: 123,
+            site_info: Some(SiteInfo::Weasyl(WeasylFile {
+                submission_id: Some(456),
+                media_id: Some(123),
+            })),
+            ..Default::default()
+        };
+        assert_eq!(file.url(), "https://www.weasyl.com/view/456/");
+
+        let file = File {
+            site_id: 123,
+            url: "https://notfuraffinity.net/image.png".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(file.site(), None);
+        assert_eq!(file.try_url(), None);
+        assert_eq!(file.url(), "https://notfuraffinity.net/image.png");
+        assert_eq!(file.id(), "Unknown-123");
+    }
+
+    #[test]
+    fn test_site_info_serde() {
+        let files: Vec<File> = serde_json::from_str(
+            r#"[
+                {"site_id": 1, "url": "", "filename": "", "artists": null, "rating": null, "posted_at": null, "tags": null, "sha256": null, "hash": null, "distance": null, "searched_hash": null, "site": "FurAffinity", "site_info": {"file_id": 2}},
+                {"site_id": 3, "url": "", "filename": "", "artists": null, "rating": null, "posted_at": null, "tags": null, "sha256": null, "hash": null, "distance": null, "searched_hash": null, "site": "Twitter", "site_info": null},
+                {"site_id": 4, "url": "", "filename": "", "artists": null, "rating": null, "posted_at": null, "tags": null, "sha256": null, "hash": null, "distance": null, "searched_hash": null, "site": "NewSite", "site_info": {"id": 5}}
+            ]"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            files[0].site_info,
+            Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 2 }))
+        ));
+        assert!(matches!(files[1].site_info, Some(SiteInfo::Twitter(_))));
+        assert!(
+            matches!(&files[2].site_info, Some(SiteInfo::Unknown { site, site_info }) if site == "NewSite" && site_info["id"] == 5)
+        );
+        assert_eq!(files[2].site_name(), "Unknown");
+        assert_eq!(files[2].id(), "NewSite-4");
+        assert_eq!(files[2].url(), "");
+
+        let json = serde_json::to_value(&files[2]).unwrap();
+        assert_eq!(json["site"], "NewSite");
+        assert_eq!(json["site_info"]["id"], 5);
+
+        let file: File = serde_json::from_value(json).unwrap();
+        assert_eq!(file.site_info.unwrap().name(), "NewSite");
+    }
+
+    #[test]
+    fn test_furaffinity_file_detail() {
+        let detail: FurAffinityFileDetail = serde_json::from_str(
+            r#"{"artist": "user", "deleted": false, "file_id": 1600000000, "file_size": null, "filename": "1600000000.user_image.png", "hash": null, "hash_str": "-5", "id": 123, "posted_at": null, "rating": "general", "sha256": null, "tags": ["tag"], "updated_at": null, "url": "https://d.furaffinity.net/art/user/1600000000/1600000000.user_image.png"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(detail.page_url(), "https://www.furaffinity.net/view/123/");
+        assert_eq!(detail.hash(), Some(Hash(-5)));
+        assert!(detail.is_available());
+
+        let file = File::from(detail);
+        assert_eq!(file.site_id, 123);
+        assert_eq!(file.hash, Some(Hash(-5)));
+        assert_eq!(file.artists, Some(vec!["user".to_string()]));
+        assert!(matches!(
+            file.site_info,
+            Some(SiteInfo::FurAffinity(FurAffinityFile {
+                file_id: 1600000000
+            }))
+        ));
+        assert_eq!(file.url(), "https://www.furaffinity.net/view/123/");
+
+        let detail: FurAffinityFileDetail = serde_json::from_str(
+            r#"{"artist": null, "deleted": true, "file_id": null, "file_size": null, "filename": null, "hash": null, "hash_str": null, "id": 123, "posted_at": null, "rating": null, "sha256": null, "tags": [], "updated_at": null, "url": null}"#,
+        )
+        .unwrap();
+        assert!(!detail.is_available());
+
+        let file = File::from(detail);
+        assert_eq!(file.site(), Some(Site::FurAffinity));
+        assert_eq!(file.url(), "https://www.furaffinity.net/view/123/");
+        assert_eq!(file.id(), "FurAffinity-123");
+    }
+
+    #[test]
+    fn test_dedup_files() {
+        let fa = || Some(SiteInfo::FurAffinity(FurAffinityFile { file_id: 1 }));
+        let weasyl = || Some(SiteInfo::Weasyl(Default::default()));
+
+        let files = vec![
+            File {
+                site_info: fa(),
+                distance: Some(5),
+                ..test_file(1, None, None)
+            },
+            File {
+                site_info: weasyl(),
+                distance: Some(4),
+                ..test_file(1, None, None)
+            },
+            File {
+                site_info: fa(),
+                distance: Some(3),
+                ..test_file(2, None, None)
+            },
+            File {
+                site_info: fa(),
+                distance: Some(2),
+                ..test_file(1, None, None)
+            },
+        ];
+
+        let deduped: Vec<_> = dedup_files(files)
+            .into_iter()
+            .map(|file| (file.site_id, file.distance))
+            .collect();
+        assert_eq!(deduped, vec![(1, Some(2)), (1, Some(4)), (2, Some(3))]);
+    }
+}